@@ -0,0 +1,163 @@
+use bevy::{prelude::*, utils::HashMap};
+use rand::Rng;
+use crate::app_state::AppState;
+use crate::pause;
+use crate::game::{self, BadThing, Carousel, CircleCollider, Difficulty, GameRng, GoodThing, Mallet, Nail, Rescue, RescueCooldown, Situation};
+use crate::generic_ui::Cooldown;
+use crate::stamina::Stamina;
+use crate::hazards::{Dormant, Rotate};
+
+#[derive(Resource)]
+pub struct AiController {
+    pub enabled: bool,
+    pub reaction_time: f32,
+    pub error_rate: f64,
+    pub margin: f32,
+    pub lookahead: f32,
+    pending: HashMap<Situation, Timer>,
+}
+
+impl AiController {
+    pub fn new(reaction_time: f32, error_rate: f64) -> Self {
+        AiController {
+            enabled: true,
+            reaction_time,
+            error_rate: error_rate.clamp(0., 1.),
+            ..default()
+        }
+    }
+}
+
+impl Default for AiController {
+    fn default() -> Self {
+        AiController {
+            enabled: false,
+            reaction_time: 0.25,
+            error_rate: 0.05,
+            margin: 40.,
+            lookahead: 1.,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<AiController>()
+        .add_systems(OnEnter(AppState::Game), forget_pending)
+        .add_systems(Update, ai_player
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+            .run_if(|ai: Res<AiController>| ai.enabled)
+        );
+    }
+}
+
+fn forget_pending(mut ai: ResMut<AiController>) {
+    ai.pending.clear();
+}
+
+fn clearance(a: Vec2, a_radius: f32, b: Vec2, b_radius: f32) -> f32 {
+    a.distance(b) - a_radius - b_radius
+}
+
+fn rotate_about(point: Vec2, centre: Vec2, angle: f32) -> Vec2 {
+    centre + Vec2::from_angle(angle).rotate(point - centre)
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn ai_player(
+    mut ai: ResMut<AiController>,
+    time: Res<Time>,
+    difficulty: Res<Difficulty>,
+    good_things: Query<(&GoodThing, &CircleCollider, &Transform)>,
+    bad_things: Query<(&CircleCollider, &GlobalTransform, Has<Nail>), (With<BadThing>, Without<Dormant>)>,
+    active_mallet: Query<(), (With<Mallet>, With<BadThing>, Without<Dormant>)>,
+    carousels: Query<(&GlobalTransform, &Rotate), With<Carousel>>,
+    mut cooldowns: Query<(&RescueCooldown, &mut Cooldown)>,
+    mut stamina: ResMut<Stamina>,
+    mut rescues: EventWriter<Rescue>,
+    mut rng: ResMut<GameRng>,
+) {
+    let mut threatened: Vec<Situation> = Vec::new();
+
+    for (good_thing, good_circle, good_transform) in &good_things {
+        let good_pos = good_transform.translation.xy();
+
+        let in_danger = match good_thing.situation {
+            Situation::Baby => bad_things.iter()
+                .filter(|(_, _, nail)| !nail)
+                .any(|(bad_circle, bad_transform, _)| {
+                    clearance(good_pos, good_circle.radius, bad_transform.translation().xy(), bad_circle.radius) < ai.margin
+                }),
+            Situation::Sloth => {
+                // pushing only clears the strike zone once the sloth is already inside it
+                let inside = clearance(good_pos, good_circle.radius, game::MALLET_STRIKE, game::MALLET_RADIUS) < 0.;
+                inside && active_mallet.is_empty()
+            },
+            Situation::Kitten => carousels.iter().any(|(carousel_transform, rotate)| {
+                let angular_velocity = rotate.angular_velocity(&difficulty);
+                let centre = carousel_transform.translation().xy();
+
+                bad_things.iter()
+                    .filter(|(_, _, nail)| *nail)
+                    .any(|(bad_circle, bad_transform, _)| {
+                        let now = bad_transform.translation().xy();
+                        let later = rotate_about(now, centre, angular_velocity * ai.lookahead);
+
+                        let clearance_now = clearance(good_pos, good_circle.radius, now, bad_circle.radius);
+                        let clearance_later = clearance(good_pos, good_circle.radius, later, bad_circle.radius);
+
+                        clearance_later < ai.margin && clearance_later < clearance_now
+                    })
+            }),
+        };
+
+        if in_danger && !threatened.contains(&good_thing.situation) {
+            threatened.push(good_thing.situation);
+        }
+    }
+
+    let reaction_time = ai.reaction_time;
+    ai.pending.retain(|situation, _| threatened.contains(situation));
+
+    for situation in threatened {
+        ai.pending
+            .entry(situation)
+            .or_insert_with(|| Timer::from_seconds(reaction_time, TimerMode::Once));
+    }
+
+    let mut acted: Vec<Situation> = Vec::new();
+
+    for (situation, timer) in ai.pending.iter_mut() {
+        if timer.tick(time.delta()).finished() {
+            acted.push(*situation);
+        }
+    }
+
+    for situation in acted {
+        // the ai presses the same buttons as a player, so it waits on the same charges and stamina
+        if !stamina.can_afford(situation) {
+            continue;
+        }
+
+        let charged = cooldowns.iter_mut()
+            .find(|(rescue, _)| rescue.situation == situation)
+            .is_none_or(|(_, mut cooldown)| cooldown.take());
+
+        if !charged {
+            continue;
+        }
+
+        ai.pending.remove(&situation);
+        stamina.spend(situation);
+
+        // drawn from the run's rng, so a seeded soak misses the same presses every time
+        if !rng.0.gen_bool(ai.error_rate) {
+            rescues.send(Rescue { situation });
+        }
+    }
+}
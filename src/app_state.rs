@@ -1,15 +1,31 @@
 use bevy::prelude::*;
+use crate::launch::LaunchConfig;
 
-#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum AppState {
+    #[default]
     MainMenu,
     Game,
     GameOver,
+    Mods,
+    History,
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        AppState::MainMenu
+impl AppState {
+    // what the console and --start-state call each state
+    pub const NAMES: [(&'static str, AppState); 5] = [
+        ("menu", AppState::MainMenu),
+        ("game", AppState::Game),
+        ("gameover", AppState::GameOver),
+        ("mods", AppState::Mods),
+        ("history", AppState::History),
+    ];
+
+    pub fn parse(name: &str) -> Result<AppState, String> {
+        AppState::NAMES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, state)| *state)
+            .ok_or_else(|| format!("unknown state {}", name))
     }
 }
 
@@ -18,5 +34,25 @@ pub struct AppStatePlugin;
 impl Plugin for AppStatePlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<AppState>();
+
+        let start_state = app.world.get_resource::<LaunchConfig>().and_then(|launch| launch.start_state);
+
+        if let Some(state) = start_state {
+            app.world.resource_mut::<NextState<AppState>>().set(state);
+        }
+
+        #[cfg(feature = "debug")]
+        {
+            use crate::console::{self, ConsoleExt};
+
+            let names = AppState::NAMES.map(|(name, _)| name);
+
+            app.console_command("state", &format!("state {}", names.join("|")), &names, |world, args| {
+                let state = AppState::parse(console::arg(args, 0, "state")?)?;
+
+                world.resource_mut::<NextState<AppState>>().set(state);
+                Ok(format!("going to {:?}", state))
+            });
+        }
     }
 }
\ No newline at end of file
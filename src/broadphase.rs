@@ -0,0 +1,246 @@
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, transform::TransformSystem, utils::HashMap};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::app_state::AppState;
+use crate::game::{BadThing, CircleCollider, CollisionSet};
+use crate::hazards::Dormant;
+
+// about the size of the hazards the base game spawns, so most land in one to four cells
+const CELL_SIZE: f32 = 128.;
+
+struct Entry<T> {
+    item: T,
+    position: Vec2,
+    radius: f32,
+    // lowest cell the entry covers, used so a query reports it once however many cells it spans
+    first_cell: IVec2,
+}
+
+// circles bucketed into square cells, so a lookup only looks at the circles around it
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+    entries: Vec<Entry<T>>,
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid { cell_size, cells: HashMap::default(), entries: Vec::new() }
+    }
+
+    // keeps the cells' allocations, the grid is refilled every frame
+    pub fn clear(&mut self) {
+        self.entries.clear();
+
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    fn cells_covering(&self, position: Vec2, radius: f32) -> (IVec2, IVec2) {
+        let cell = |point: Vec2| (point / self.cell_size).floor().as_ivec2();
+        (cell(position - Vec2::splat(radius)), cell(position + Vec2::splat(radius)))
+    }
+
+    pub fn insert(&mut self, item: T, position: Vec2, radius: f32) {
+        let (from, to) = self.cells_covering(position, radius);
+        let index = self.entries.len();
+
+        self.entries.push(Entry { item, position, radius, first_cell: from });
+
+        for x in from.x..=to.x {
+            for y in from.y..=to.y {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    // every circle overlapping this one, with its position and radius
+    pub fn overlapping(&self, position: Vec2, radius: f32) -> impl Iterator<Item = (T, Vec2, f32)> + '_ {
+        let (from, to) = self.cells_covering(position, radius);
+
+        (from.x..=to.x)
+            .flat_map(move |x| (from.y..=to.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell).map(|indices| (cell, indices)))
+            .flat_map(move |(cell, indices)| indices.iter().map(move |index| (cell, &self.entries[*index])))
+            // an entry spanning several cells is only reported from the first one both it and the query cover
+            .filter(move |(cell, entry)| *cell == entry.first_cell.max(from))
+            .filter(move |(_, entry)| entry.position.distance(position) <= entry.radius + radius)
+            .map(|(_, entry)| (entry.item, entry.position, entry.radius))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hazard {
+    pub dormant: bool,
+}
+
+// every bad thing as of this frame's transforms, for the collision check and anything else asking what's nearby
+#[derive(Resource)]
+pub struct HazardGrid(pub SpatialGrid<Hazard>);
+
+impl Default for HazardGrid {
+    fn default() -> Self {
+        HazardGrid(SpatialGrid::new(CELL_SIZE))
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BroadphaseSet;
+
+pub struct BroadphasePlugin;
+
+impl Plugin for BroadphasePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<HazardGrid>()
+        .add_systems(PostUpdate, fill_hazard_grid
+            .in_set(BroadphaseSet)
+            .after(TransformSystem::TransformPropagate)
+            .before(CollisionSet)
+            .run_if(in_state(AppState::Game))
+        )
+        .add_systems(OnExit(AppState::Game), clear_hazard_grid);
+    }
+}
+
+fn fill_hazard_grid(mut grid: ResMut<HazardGrid>, bad_things: Query<(&CircleCollider, &GlobalTransform, Has<Dormant>), With<BadThing>>) {
+    grid.0.clear();
+
+    for (circle, transform, dormant) in &bad_things {
+        let radius = circle.radius * transform.compute_transform().scale.x;
+        grid.0.insert(Hazard { dormant }, transform.translation().xy(), radius);
+    }
+}
+
+fn clear_hazard_grid(mut grid: ResMut<HazardGrid>) {
+    grid.0.clear();
+}
+
+// do_something bench [counts...]
+// times one frame of collision checks both ways at growing crowd sizes, the tests below check they agree
+pub fn bench(args: &[String]) {
+    const FRAMES: u32 = 20;
+
+    let mut counts: Vec<usize> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+
+    if counts.is_empty() {
+        counts = vec![100, 300, 1000, 3000];
+    }
+
+    println!("{:>8} {:>12} {:>12} {:>8} {:>8}", "things", "pairs ms", "grid ms", "speedup", "hits");
+
+    for count in counts {
+        let mut rng = ChaCha8Rng::seed_from_u64(count as u64);
+
+        // the field grows with the crowd so it stays about as busy as chaos mode
+        let side = (count as f32).sqrt() * 150.;
+        let mut circle = || (Vec2::new(rng.gen_range(0. ..side), rng.gen_range(0. ..side)), rng.gen_range(10. ..50.));
+
+        let good: Vec<(Vec2, f32)> = (0..count).map(|_| circle()).collect();
+        let bad: Vec<(Vec2, f32)> = (0..count * 2).map(|_| circle()).collect();
+
+        let mut pairs_hits = 0;
+        let mut pairs_time = Duration::ZERO;
+
+        for _ in 0..FRAMES {
+            let start = Instant::now();
+
+            pairs_hits = good.iter()
+                .map(|(good_pos, good_radius)| bad.iter()
+                    .filter(|(bad_pos, bad_radius)| good_pos.distance(*bad_pos) <= good_radius + bad_radius)
+                    .count())
+                .sum::<usize>();
+
+            pairs_time += start.elapsed();
+        }
+
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        let mut grid_hits = 0;
+        let mut grid_time = Duration::ZERO;
+
+        for _ in 0..FRAMES {
+            let start = Instant::now();
+
+            grid.clear();
+
+            for (index, (position, radius)) in bad.iter().enumerate() {
+                grid.insert(index, *position, *radius);
+            }
+
+            grid_hits = good.iter()
+                .map(|(position, radius)| grid.overlapping(*position, *radius).count())
+                .sum::<usize>();
+
+            grid_time += start.elapsed();
+        }
+
+        let pairs_ms = pairs_time.as_secs_f64() * 1000. / FRAMES as f64;
+        let grid_ms = grid_time.as_secs_f64() * 1000. / FRAMES as f64;
+
+        println!("{:>8} {:>12.3} {:>12.3} {:>7.1}x {:>8}", count * 3, pairs_ms, grid_ms, pairs_ms / grid_ms, pairs_hits.max(grid_hits));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(grid: &SpatialGrid<usize>, position: Vec2, radius: f32) -> Vec<usize> {
+        let mut found: Vec<usize> = grid.overlapping(position, radius).map(|(index, _, _)| index).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn agrees_with_checking_every_pair() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+
+        // radii up to a few cells across, so plenty of circles and queries span several cells
+        let mut circle = || (Vec2::new(rng.gen_range(-2000. ..2000.), rng.gen_range(-2000. ..2000.)), rng.gen_range(1. ..CELL_SIZE * 3.));
+
+        let bad: Vec<(Vec2, f32)> = (0..500).map(|_| circle()).collect();
+        let good: Vec<(Vec2, f32)> = (0..200).map(|_| circle()).collect();
+
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+
+        for (index, (position, radius)) in bad.iter().enumerate() {
+            grid.insert(index, *position, *radius);
+        }
+
+        for (position, radius) in &good {
+            let expected: Vec<usize> = bad.iter()
+                .enumerate()
+                .filter(|(_, (bad_pos, bad_radius))| position.distance(*bad_pos) <= radius + bad_radius)
+                .map(|(index, _)| index)
+                .collect();
+
+            assert_eq!(found(&grid, *position, *radius), expected);
+        }
+    }
+
+    #[test]
+    fn reports_a_circle_spanning_many_cells_once() {
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        grid.insert(0, Vec2::new(10., -10.), CELL_SIZE * 2.5);
+
+        // from inside, and from queries overlapping different corners of it
+        assert_eq!(found(&grid, Vec2::ZERO, CELL_SIZE * 2.), vec![0]);
+        assert_eq!(found(&grid, Vec2::new(CELL_SIZE * 2.5, CELL_SIZE * 1.5), CELL_SIZE), vec![0]);
+        assert_eq!(found(&grid, Vec2::new(-CELL_SIZE * 2., -CELL_SIZE * 2.), CELL_SIZE), vec![0]);
+    }
+
+    #[test]
+    fn touching_counts_and_clear_forgets() {
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        grid.insert(0, Vec2::ZERO, 50.);
+
+        assert_eq!(found(&grid, Vec2::new(100., 0.), 50.), vec![0]);
+        assert!(found(&grid, Vec2::new(100.5, 0.), 50.).is_empty());
+
+        grid.clear();
+        assert!(found(&grid, Vec2::ZERO, 50.).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+use bevy::{prelude::*, render::camera::ScalingMode, window::PrimaryWindow};
+use crate::app_state::AppState;
+use crate::combo::NearMiss;
+use crate::game::{Collision, CollisionSet, Mallet};
+use crate::hazards::Dormant;
+use crate::launch::LaunchConfig;
+use crate::pause::Frozen;
+use crate::startup;
+
+const MAX_SHAKE: f32 = 14.;
+// trauma lost per second, shake strength is trauma squared so small knocks stay small
+const TRAUMA_DECAY: f32 = 1.5;
+const FINALE_SECONDS: f32 = 1.2;
+// room left around the colliding pair when zooming in on them
+const FINALE_MARGIN: f32 = 300.;
+const FINALE_MAX_ZOOM: f32 = 3.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scaling {
+    // the whole playfield is always visible, with bars if the aspect differs
+    #[default]
+    Fit,
+    // the window is always covered, cropping the playfield if the aspect differs
+    Fill,
+    // whole-number pixel scale only, cropping whatever doesn't fit
+    PixelPerfect,
+}
+
+// reduced motion turns off the shake and the zoom at the end of a run
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CameraSettings {
+    pub scaling: Scaling,
+    pub reduced_motion: bool,
+}
+
+#[derive(Event)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+// the view the camera wants, shake is added on top of it every frame
+#[derive(Component)]
+pub struct CameraRig {
+    pub focus: Vec2,
+    pub zoom: f32,
+    trauma: f32,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        CameraRig { focus: Vec2::ZERO, zoom: 1., trauma: 0. }
+    }
+}
+
+// the camera lingering on a collision before the game over screen
+#[derive(Resource)]
+struct Finale {
+    timer: Timer,
+    from: (Vec2, f32),
+    to: (Vec2, f32),
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        let launch = app.world.get_resource_or_insert_with(LaunchConfig::default).clone();
+
+        app
+        .insert_resource(CameraSettings { scaling: launch.scaling, reduced_motion: launch.reduced_motion })
+        .add_event::<CameraShake>()
+        .add_systems(Update, (apply_scaling, shake_on_events, play_finale, move_camera).chain())
+        .add_systems(PostUpdate, start_finale.after(CollisionSet).run_if(in_state(AppState::Game)))
+        .add_systems(OnExit(AppState::Game), reset_camera);
+    }
+}
+
+fn apply_scaling(
+    settings: Res<CameraSettings>,
+    windows: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut projections: Query<&mut OrthographicProjection, With<CameraRig>>,
+) {
+    let window = windows.get_single().ok();
+
+    if window.is_none() && !settings.is_changed() {
+        return;
+    }
+
+    let (width, height) = startup::RESOLUTION;
+
+    let scaling_mode = match settings.scaling {
+        Scaling::Fit => ScalingMode::AutoMin { min_width: width, min_height: height },
+        Scaling::Fill => ScalingMode::AutoMax { max_width: width, max_height: height },
+        Scaling::PixelPerfect => {
+            let scale = window
+                .map(|window| (window.width() / width).min(window.height() / height).floor())
+                .unwrap_or(1.)
+                .max(1.);
+
+            ScalingMode::WindowSize(scale)
+        },
+    };
+
+    for mut projection in &mut projections {
+        projection.scaling_mode = scaling_mode.clone();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn shake_on_events(
+    mut shakes: EventReader<CameraShake>,
+    mut near_misses: EventReader<NearMiss>,
+    mut collisions: EventReader<Collision>,
+    mut woken: RemovedComponents<Dormant>,
+    mallets: Query<(), With<Mallet>>,
+    mut rigs: Query<&mut CameraRig>,
+    settings: Res<CameraSettings>,
+) {
+    let mut trauma: f32 = shakes.read().map(|shake| shake.trauma).sum();
+
+    trauma += near_misses.read().count() as f32 * 0.25;
+    trauma += collisions.read().count() as f32 * 0.6;
+    // a mallet waking up is a mallet coming down
+    trauma += woken.read().filter(|entity| mallets.contains(*entity)).count() as f32 * 0.4;
+
+    if settings.reduced_motion || trauma <= 0. {
+        return;
+    }
+
+    for mut rig in &mut rigs {
+        rig.trauma = (rig.trauma + trauma).min(1.);
+    }
+}
+
+// shake keeps going while the game is paused or frozen, so it runs on real time
+fn move_camera(mut cameras: Query<(&mut CameraRig, &mut Transform, &mut OrthographicProjection)>, time: Res<Time<Real>>) {
+    let now = time.elapsed_seconds();
+
+    for (mut rig, mut transform, mut projection) in &mut cameras {
+        rig.trauma = (rig.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.);
+
+        let strength = rig.trauma * rig.trauma * MAX_SHAKE;
+        // a few sines at odd frequencies wobble convincingly without touching the game's rng
+        let offset = Vec2::new(
+            (now * 37.).sin() + (now * 61.).sin() * 0.5,
+            (now * 43.).cos() + (now * 53.).sin() * 0.5,
+        ) * strength / 1.5;
+
+        let translation = (rig.focus + offset).extend(transform.translation.z);
+
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+
+        let scale = 1. / rig.zoom;
+
+        if projection.scale != scale {
+            projection.scale = scale;
+        }
+    }
+}
+
+fn start_finale(
+    mut commands: Commands,
+    mut collisions: EventReader<Collision>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut frozen: ResMut<Frozen>,
+    settings: Res<CameraSettings>,
+    rigs: Query<&CameraRig>,
+) {
+    let Some(collision) = collisions.read().last() else {
+        return;
+    };
+
+    let Ok(rig) = rigs.get_single() else {
+        return;
+    };
+
+    if settings.reduced_motion || next_state.0 != Some(AppState::GameOver) {
+        return;
+    }
+
+    // hold the game over back until the camera has shown what happened
+    next_state.0 = None;
+    frozen.0 = true;
+
+    let (width, height) = startup::RESOLUTION;
+    let span = (collision.good - collision.bad).abs() + Vec2::splat(FINALE_MARGIN);
+    let zoom = (width / span.x).min(height / span.y).clamp(1., FINALE_MAX_ZOOM);
+
+    commands.insert_resource(Finale {
+        timer: Timer::from_seconds(FINALE_SECONDS, TimerMode::Once),
+        from: (rig.focus, rig.zoom),
+        to: ((collision.good + collision.bad) / 2., zoom),
+    });
+}
+
+fn play_finale(
+    mut commands: Commands,
+    finale: Option<ResMut<Finale>>,
+    mut rigs: Query<&mut CameraRig>,
+    mut next_state: ResMut<NextState<AppState>>,
+    time: Res<Time<Real>>,
+) {
+    let Some(mut finale) = finale else {
+        return;
+    };
+
+    finale.timer.tick(time.delta());
+
+    let t = finale.timer.percent();
+    let eased = t * t * (3. - 2. * t);
+
+    for mut rig in &mut rigs {
+        rig.focus = finale.from.0.lerp(finale.to.0, eased);
+        rig.zoom = finale.from.1 + (finale.to.1 - finale.from.1) * eased;
+    }
+
+    if finale.timer.finished() {
+        commands.remove_resource::<Finale>();
+        next_state.set(AppState::GameOver);
+    }
+}
+
+fn reset_camera(mut commands: Commands, mut rigs: Query<&mut CameraRig>, mut frozen: ResMut<Frozen>) {
+    commands.remove_resource::<Finale>();
+    frozen.0 = false;
+
+    for mut rig in &mut rigs {
+        *rig = CameraRig::default();
+    }
+}
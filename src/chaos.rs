@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::app_state::AppState;
+use crate::broadphase::SpatialGrid;
+use crate::camera::CameraRig;
+use crate::game::{BadThing, Carousel, CircleCollider, GameRng, GameSetup, GoodThing, Nail, Situation, CAROUSEL_SPEED};
+use crate::hazards::{Grow, Rotate};
+use crate::launch::LaunchConfig;
+use crate::motion::{FallAndWrap, Impulse, InvertVelocity, LinearDrift, RescuedBy, ResetTo};
+use crate::startup;
+use crate::util;
+
+// chaos copies are drawn this much smaller than the real things
+const SCALE: f32 = 0.25;
+// room kept clear around each copy when they're scattered
+const SPACING: f32 = 80.;
+const PLACEMENT_ATTEMPTS: u32 = 30;
+
+// on everything chaos mode spawns, so it is cleaned up on its own and kept out of saves and per-thing ui
+#[derive(Component)]
+pub struct Swarm;
+
+#[derive(Resource)]
+struct Chaos {
+    // copies of each of the baby and fire, sloth, and nail carousel
+    count: u32,
+}
+
+pub struct ChaosPlugin;
+
+impl Plugin for ChaosPlugin {
+    fn build(&self, app: &mut App) {
+        let launch = app.world.get_resource_or_insert_with(LaunchConfig::default).clone();
+
+        let Some(count) = launch.chaos else {
+            return;
+        };
+
+        app
+        .insert_resource(Chaos { count })
+        .add_systems(OnEnter(AppState::Game), spawn_swarm.after(GameSetup))
+        .add_systems(OnExit(AppState::Game), util::cleanup_system::<Swarm>);
+    }
+}
+
+fn scaled(mut sprite: SpriteBundle) -> SpriteBundle {
+    sprite.transform.scale = Vec3::splat(SCALE);
+    sprite
+}
+
+fn spawn_swarm(
+    mut commands: Commands,
+    chaos: Res<Chaos>,
+    asset_server: Res<AssetServer>,
+    mut rng: ResMut<GameRng>,
+    mut rigs: Query<&mut CameraRig>,
+) {
+    let things = chaos.count as f32 * 4.;
+
+    // the field grows with the swarm, keeping the playfield's shape, and the camera pulls back to fit it
+    let (width, height) = startup::RESOLUTION;
+    let stretch = (things * SPACING * SPACING * 2. / (width * height)).sqrt().max(1.);
+    let half = Vec2::new(width, height) * stretch / 2.;
+
+    for mut rig in &mut rigs {
+        rig.zoom = 1. / stretch;
+    }
+
+    // seeded from the run's rng so a chaos run replays like any other
+    let mut placed = SpatialGrid::new(SPACING * 2.);
+    let mut place = |rng: &mut GameRng| {
+        let mut position = Vec2::ZERO;
+
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            position = Vec2::new(rng.0.gen_range(-half.x..half.x), rng.0.gen_range(-half.y..half.y));
+
+            if placed.overlapping(position, SPACING / 2.).next().is_none() {
+                break;
+            }
+        }
+
+        // a crowded field takes the last try, overlap and all
+        placed.insert((), position, SPACING / 2.);
+        position
+    };
+
+    for _ in 0..chaos.count {
+        let position = place(&mut rng);
+
+        commands.spawn((
+            scaled(util::image(position, "baby.png".into(), &asset_server)),
+            Swarm,
+            GoodThing { situation: Situation::Baby },
+            CircleCollider { radius: 100. },
+            LinearDrift { velocity: Vec2::new(10., 0.), scale_by_difficulty: Some(Situation::Baby) },
+            RescuedBy(Situation::Baby),
+            ResetTo { x: Some(position.x), y: None },
+        ));
+
+        let position = place(&mut rng);
+
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(1.)).with_scale(Vec3::splat(SCALE))),
+            Swarm,
+            BadThing,
+            CircleCollider { radius: 100. },
+        )).with_children(|parent| {
+            parent.spawn((
+                SpriteBundle { texture: asset_server.load("fire.png"), ..default() },
+                Grow { from: 1., to: 1.06, seconds: 0.4, phase: rng.0.gen_range(0. ..0.4), ping_pong: true },
+            ));
+        });
+
+        let position = place(&mut rng);
+
+        commands.spawn((
+            scaled(util::image(position, "sloth.png".into(), &asset_server)),
+            Swarm,
+            GoodThing { situation: Situation::Sloth },
+            CircleCollider { radius: 50. },
+            FallAndWrap { speed: 7., top: half.y, bottom: -half.y },
+            RescuedBy(Situation::Sloth),
+            Impulse { offset: Vec2::new(0., -200. * SCALE), seconds: 0.25 },
+        ));
+
+        let position = place(&mut rng);
+
+        commands.spawn((
+            scaled(util::image_low(position, "carousel.png".into(), &asset_server)),
+            Swarm,
+            Carousel,
+            Rotate { speed: CAROUSEL_SPEED, direction: 1., scale_by: Some(Situation::Kitten) },
+            RescuedBy(Situation::Kitten),
+            InvertVelocity,
+        )).with_children(|parent| {
+            parent.spawn(util::image(Vec2::new(0., 125.), "bridge.png".into(), &asset_server));
+            parent.spawn(util::image_rot(Vec2::new(-91., -72.), "bridge.png".into(), &asset_server, 2.3));
+
+            parent.spawn((
+                util::image_rot(Vec2::new(125. / 1.414, -125. / 1.414), "nails.png".into(), &asset_server, 2. * std::f32::consts::TAU / 3.),
+                BadThing,
+                Nail,
+                CircleCollider { radius: 50. },
+            ));
+        });
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::broadphase::HazardGrid;
+use crate::game::{CircleCollider, GameEntity, GameSetup, GoodThing, Rescue, Score, Situation};
+use crate::pause;
+use crate::theme::UiTheme;
+use crate::tween::{self, Ease, Lens, Tween, TweenCompleted};
+
+// a rescue this close to touching a live hazard counts as a near miss
+const NEAR_MISS_MARGIN: f32 = 40.;
+const NEAR_MISS_POINTS: f32 = 5.;
+const COMBO_STEP: f32 = 0.5;
+const COMBO_MAX: f32 = 5.;
+// seconds the multiplier holds before it starts falling back to 1
+const COMBO_GRACE: f32 = 2.;
+const COMBO_DECAY: f32 = 0.5;
+const POPUP_TWEEN: u32 = 40;
+
+#[derive(Resource, Debug, Clone)]
+pub struct Combo {
+    pub multiplier: f32,
+    idle: f32,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Combo { multiplier: 1., idle: 0. }
+    }
+}
+
+// where the score came from, survival is whatever the bonus doesn't account for
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    pub bonus: i32,
+    pub near_misses: u32,
+    pub best_combo: f32,
+}
+
+impl ScoreBreakdown {
+    pub fn survival(&self, score: &Score) -> i32 {
+        score.0 - self.bonus
+    }
+}
+
+#[derive(Event)]
+pub struct NearMiss {
+    pub situation: Situation,
+    pub points: i32,
+    pub multiplier: f32,
+    pub position: Vec2,
+}
+
+#[derive(Component)]
+struct Popup;
+
+pub struct ComboPlugin;
+
+impl Plugin for ComboPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Combo>()
+        .init_resource::<ScoreBreakdown>()
+        .add_event::<NearMiss>()
+        .add_systems(OnEnter(AppState::Game), reset_combo.before(GameSetup))
+        .add_systems(Update, (detect_near_misses, decay_combo, spawn_popups)
+            .chain()
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        )
+        .add_systems(Update, remove_popups);
+    }
+}
+
+fn reset_combo(mut combo: ResMut<Combo>, mut breakdown: ResMut<ScoreBreakdown>) {
+    *combo = Combo::default();
+    *breakdown = ScoreBreakdown::default();
+}
+
+#[allow(clippy::type_complexity)]
+fn detect_near_misses(
+    mut rescues: EventReader<Rescue>,
+    mut near_misses: EventWriter<NearMiss>,
+    mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+    mut breakdown: ResMut<ScoreBreakdown>,
+    good_things: Query<(&GoodThing, &CircleCollider, &GlobalTransform)>,
+    hazards: Res<HazardGrid>,
+) {
+    for rescue in rescues.read() {
+        for (good_thing, good_circle, good_transform) in &good_things {
+            if good_thing.situation != rescue.situation {
+                continue;
+            }
+
+            let reach = good_circle.radius * good_transform.compute_transform().scale.x + NEAR_MISS_MARGIN;
+            // a dormant hazard can't hurt anything, so dodging it isn't a near miss
+            let close = hazards.0.overlapping(good_transform.translation().xy(), reach).any(|(hazard, _, _)| !hazard.dormant);
+
+            if !close {
+                continue;
+            }
+
+            let points = (NEAR_MISS_POINTS * combo.multiplier).round() as i32;
+
+            near_misses.send(NearMiss {
+                situation: good_thing.situation,
+                points,
+                multiplier: combo.multiplier,
+                position: good_transform.translation().xy(),
+            });
+
+            score.0 += points;
+            breakdown.bonus += points;
+            breakdown.near_misses += 1;
+            breakdown.best_combo = breakdown.best_combo.max(combo.multiplier);
+
+            combo.multiplier = (combo.multiplier + COMBO_STEP).min(COMBO_MAX);
+            combo.idle = 0.;
+        }
+    }
+}
+
+fn decay_combo(mut combo: ResMut<Combo>, time: Res<Time>) {
+    if combo.multiplier <= 1. {
+        return;
+    }
+
+    combo.idle += time.delta_seconds();
+
+    if combo.idle > COMBO_GRACE {
+        combo.multiplier = (combo.multiplier - COMBO_DECAY * time.delta_seconds()).max(1.);
+    }
+}
+
+fn spawn_popups(mut commands: Commands, mut near_misses: EventReader<NearMiss>, theme: Res<UiTheme>) {
+    for near_miss in near_misses.read() {
+        let text = match near_miss.multiplier > 1. {
+            true => format!("+{} x{:.1}", near_miss.points, near_miss.multiplier),
+            false => format!("+{}", near_miss.points),
+        };
+
+        let popup = commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(text, theme.text_style(theme.text_size, theme.focus)),
+                transform: Transform::from_translation(near_miss.position.extend(2.)),
+                ..default()
+            },
+            Popup,
+            GameEntity,
+        )).id();
+
+        let mut faded = theme.focus;
+        faded.set_a(0.);
+
+        commands.add(tween::tween(popup, Tween::new(Lens::TranslateBy(Vec3::new(0., 80., 0.)), 1., Ease::QuadOut)));
+        commands.add(tween::tween(popup, Tween::new(Lens::TextColour { from: theme.focus, to: faded }, 1., Ease::QuadIn).with_id(POPUP_TWEEN)));
+    }
+}
+
+fn remove_popups(mut commands: Commands, mut completed: EventReader<TweenCompleted>, popups: Query<(), With<Popup>>) {
+    for completed in completed.read() {
+        if completed.id == POPUP_TWEEN && popups.contains(completed.entity) {
+            commands.entity(completed.entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use bevy::{prelude::*, window::PresentMode};
+use serde::{Deserialize, Serialize};
+use crate::game::DifficultyProfile;
+use crate::launch::{self, LaunchConfig, WindowChoice};
+use crate::startup;
+use crate::theme::UiTheme;
+
+const FILE: &str = "config.toml";
+
+// the settings file kiosk builds ship with, everything in it is optional
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub audio: AudioConfig,
+    pub gameplay: GameplayConfig,
+    pub accessibility: AccessibilityConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: f32,
+    pub height: f32,
+    pub fullscreen: bool,
+    // fit, fill or pixel_perfect
+    pub scaling: String,
+    // auto_vsync, auto_no_vsync, fifo or immediate
+    pub present_mode: String,
+    pub clear_colour: String,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: startup::TITLE.into(),
+            width: startup::RESOLUTION.0,
+            height: startup::RESOLUTION.1,
+            fullscreen: false,
+            scaling: "fit".into(),
+            present_mode: "auto_vsync".into(),
+            clear_colour: hex(UiTheme::CLEAR_COLOUR),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    // an asset path, or empty for no music
+    pub music: String,
+    pub volume: f32,
+    pub mute: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            music: startup::MUSIC.into(),
+            volume: 1.,
+            mute: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameplayConfig {
+    pub mode: String,
+    pub difficulty_preset: String,
+    pub telemetry: bool,
+}
+
+impl Default for GameplayConfig {
+    fn default() -> Self {
+        GameplayConfig {
+            mode: "solo".into(),
+            difficulty_preset: DifficultyProfile::default().name,
+            telemetry: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessibilityConfig {
+    pub reduced_motion: bool,
+}
+
+// rounded rather than as_rgba_u8's truncation, so the colour reads back exactly
+fn hex(colour: Color) -> String {
+    let [r, g, b, _] = colour.as_rgba_f32().map(|channel| (channel * 255.).round() as u8);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn parse_present_mode(value: &str) -> Result<PresentMode, String> {
+    match value {
+        "auto_vsync" => Ok(PresentMode::AutoVsync),
+        "auto_no_vsync" => Ok(PresentMode::AutoNoVsync),
+        "fifo" => Ok(PresentMode::Fifo),
+        "immediate" => Ok(PresentMode::Immediate),
+        _ => Err(format!("window.present_mode should be auto_vsync, auto_no_vsync, fifo or immediate, got {}", value)),
+    }
+}
+
+impl Config {
+    // every problem at once, so a broken file can be fixed in one go, with the settings that are fine still applied
+    pub fn launch(&self) -> (LaunchConfig, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut launch = LaunchConfig::default();
+
+        match self.window.width >= 1. && self.window.height >= 1. {
+            true => launch.size = Vec2::new(self.window.width, self.window.height),
+            false => errors.push(format!("window size must be at least 1x1, got {}x{}", self.window.width, self.window.height)),
+        }
+
+        launch.title = self.window.title.clone();
+
+        launch.window = match self.window.fullscreen {
+            true => WindowChoice::Fullscreen,
+            false => WindowChoice::Windowed,
+        };
+
+        match launch::parse_scaling(&self.window.scaling) {
+            Ok(scaling) => launch.scaling = scaling,
+            Err(_) => errors.push(format!("window.scaling should be fit, fill or pixel_perfect, got {}", self.window.scaling)),
+        }
+
+        match parse_present_mode(&self.window.present_mode) {
+            Ok(present_mode) => launch.present_mode = present_mode,
+            Err(error) => errors.push(error),
+        }
+
+        match Color::hex(&self.window.clear_colour) {
+            Ok(colour) => launch.clear_colour = colour,
+            Err(_) => errors.push(format!("window.clear_colour should be a hex colour like #9a6e3d, got {}", self.window.clear_colour)),
+        }
+
+        match (0. ..=1.).contains(&self.audio.volume) {
+            true => launch.volume = self.audio.volume,
+            false => errors.push(format!("audio.volume must be between 0 and 1, got {}", self.audio.volume)),
+        }
+
+        launch.mute = self.audio.mute;
+        launch.music = Some(self.audio.music.clone()).filter(|music| !music.is_empty());
+
+        match launch::parse_mode(&self.gameplay.mode) {
+            Ok(mode) => launch.mode = Some(mode),
+            Err(_) => errors.push(format!("gameplay.mode should be solo or coop, got {}", self.gameplay.mode)),
+        }
+
+        match DifficultyProfile::preset(&self.gameplay.difficulty_preset) {
+            Some(preset) => launch.difficulty_preset = Some(preset),
+            None => {
+                let names: Vec<String> = DifficultyProfile::presets().into_iter().map(|preset| preset.name).collect();
+                errors.push(format!("gameplay.difficulty_preset should be one of {}, got {}", names.join(", "), self.gameplay.difficulty_preset));
+            },
+        }
+
+        launch.telemetry = self.gameplay.telemetry;
+        launch.reduced_motion = self.accessibility.reduced_motion;
+
+        (launch, errors)
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("do_something").join(FILE))
+}
+
+fn write_defaults(path: &Path) {
+    let written = path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| toml::to_string_pretty(&Config::default()).map_err(std::io::Error::other))
+        .and_then(|text| fs::write(path, text));
+
+    if let Err(error) = written {
+        eprintln!("could not write default config to {}: {}", path.display(), error);
+    }
+}
+
+// runs before the app exists, so problems go to stderr rather than the log
+pub fn load() -> LaunchConfig {
+    let Some(path) = path() else {
+        return LaunchConfig::default();
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            write_defaults(&path);
+            return LaunchConfig::default();
+        },
+        Err(error) => {
+            eprintln!("could not read {}, using defaults: {}", path.display(), error);
+            return LaunchConfig::default();
+        },
+    };
+
+    let config = match toml::from_str::<Config>(&text) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{} is not valid, using defaults:\n{}", path.display(), error);
+            return LaunchConfig::default();
+        },
+    };
+
+    let (launch, errors) = config.launch();
+
+    if !errors.is_empty() {
+        eprintln!("{} has invalid settings, those keep their defaults:", path.display());
+
+        for error in errors {
+            eprintln!("  {}", error);
+        }
+    }
+
+    launch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::players::GameMode;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn launched(config: &Config) -> LaunchConfig {
+        let (launch, errors) = config.launch();
+        assert!(errors.is_empty(), "{:?}", errors);
+        launch
+    }
+
+    #[test]
+    fn defaults_launch_like_no_file() {
+        let launch = launched(&Config::default());
+        let defaults = LaunchConfig::default();
+
+        assert_eq!(launch.title, defaults.title);
+        assert_eq!(launch.size, defaults.size);
+        assert_eq!(launch.window, defaults.window);
+        assert_eq!(launch.scaling, defaults.scaling);
+        assert_eq!(launch.present_mode, defaults.present_mode);
+        assert_eq!(launch.clear_colour, defaults.clear_colour);
+        assert_eq!(launch.music, defaults.music);
+        assert_eq!(launch.volume, defaults.volume);
+    }
+
+    #[test]
+    fn default_clear_colour_is_the_theme_one() {
+        assert_eq!(WindowConfig::default().clear_colour, "#9a6e3d");
+        assert_eq!(launched(&Config::default()).clear_colour, UiTheme::CLEAR_COLOUR);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let config: Config = toml::from_str(r##"
+            [window]
+            width = 0
+            scaling = "stretch"
+            present_mode = "sometimes"
+            clear_colour = "brown"
+
+            [audio]
+            volume = 1.5
+
+            [gameplay]
+            mode = "versus"
+            difficulty_preset = "nightmare"
+        "##).unwrap();
+
+        assert_eq!(config.launch().1, vec![
+            "window size must be at least 1x1, got 0x720".to_string(),
+            "window.scaling should be fit, fill or pixel_perfect, got stretch".to_string(),
+            "window.present_mode should be auto_vsync, auto_no_vsync, fifo or immediate, got sometimes".to_string(),
+            "window.clear_colour should be a hex colour like #9a6e3d, got brown".to_string(),
+            "audio.volume must be between 0 and 1, got 1.5".to_string(),
+            "gameplay.mode should be solo or coop, got versus".to_string(),
+            "gameplay.difficulty_preset should be one of gentle, standard, brisk, frantic, got nightmare".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn one_bad_value_keeps_the_rest() {
+        let config: Config = toml::from_str(r#"
+            [window]
+            title = "kiosk"
+            width = 800
+            height = 600
+            fullscreen = true
+            clear_colour = "brown"
+
+            [audio]
+            volume = 0.5
+        "#).unwrap();
+
+        let (launch, errors) = config.launch();
+
+        assert_eq!(errors, vec!["window.clear_colour should be a hex colour like #9a6e3d, got brown".to_string()]);
+        assert_eq!(launch.clear_colour, UiTheme::CLEAR_COLOUR);
+        assert_eq!(launch.title, "kiosk");
+        assert_eq!(launch.size, Vec2::new(800., 600.));
+        assert_eq!(launch.window, WindowChoice::Fullscreen);
+        assert_eq!(launch.volume, 0.5);
+    }
+
+    #[test]
+    fn bad_numbers_keep_their_defaults() {
+        let config: Config = toml::from_str("[window]\nwidth = 0\n[audio]\nvolume = 2").unwrap();
+        let (launch, errors) = config.launch();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(launch.size, LaunchConfig::default().size);
+        assert_eq!(launch.volume, LaunchConfig::default().volume);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("[window]\nfulscreen = true").is_err());
+        assert!(toml::from_str::<Config>("[sound]\nvolume = 0.5").is_err());
+    }
+
+    #[test]
+    fn flags_layer_over_the_file() {
+        let config: Config = toml::from_str(r#"
+            [window]
+            width = 800
+            height = 600
+            fullscreen = true
+            scaling = "fill"
+
+            [audio]
+            music = ""
+            mute = true
+
+            [gameplay]
+            mode = "coop"
+            difficulty_preset = "gentle"
+        "#).unwrap();
+
+        let launch = launched(&config)
+            .with_args(&args("--windowed --size 1024x768 --difficulty-preset frantic --seed 9"))
+            .unwrap();
+
+        // overridden by the command line
+        assert_eq!(launch.window, WindowChoice::Windowed);
+        assert_eq!(launch.size, Vec2::new(1024., 768.));
+        assert_eq!(launch.difficulty_preset.map(|preset| preset.name), Some("frantic".to_string()));
+        assert_eq!(launch.seed, Some(9));
+
+        // kept from the file
+        assert_eq!(launch.scaling, crate::camera::Scaling::Fill);
+        assert_eq!(launch.mode, Some(GameMode::Coop));
+        assert_eq!(launch.music, None);
+        assert!(launch.mute);
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+use bevy::{input::InputSystem, prelude::*, window::ReceivedCharacter};
+use crate::generic_ui;
+use crate::theme::UiTheme;
+
+const TOGGLE_KEY: KeyCode = KeyCode::Grave;
+const OUTPUT_LINES: usize = 10;
+
+type Run = Arc<dyn Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync>;
+
+pub struct ConsoleCommand {
+    pub help: String,
+    // offered by tab for the first argument
+    pub completions: Vec<String>,
+    run: Run,
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(BTreeMap<String, ConsoleCommand>);
+
+impl ConsoleCommands {
+    // for commands whose arguments aren't all known when they're registered
+    pub fn complete(&mut self, name: &str, completions: Vec<String>) {
+        if let Some(command) = self.0.get_mut(name) {
+            command.completions = completions;
+        }
+    }
+}
+
+// each plugin registers its own commands, the console only knows how to run them
+pub trait ConsoleExt {
+    fn console_command(
+        &mut self,
+        name: &str,
+        help: &str,
+        completions: &[&str],
+        run: impl Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl ConsoleExt for App {
+    fn console_command(
+        &mut self,
+        name: &str,
+        help: &str,
+        completions: &[&str],
+        run: impl Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        let command = ConsoleCommand {
+            help: help.into(),
+            completions: completions.iter().map(|completion| completion.to_string()).collect(),
+            run: Arc::new(run),
+        };
+
+        self.world.get_resource_or_insert_with(ConsoleCommands::default).0.insert(name.into(), command);
+        self
+    }
+}
+
+// commands read their arguments with these so every error reads the same
+pub fn arg<'a>(args: &[&'a str], index: usize, name: &str) -> Result<&'a str, String> {
+    args.get(index).copied().ok_or_else(|| format!("missing {}", name))
+}
+
+pub fn parse_arg<T: std::str::FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let value = arg(args, index, name)?;
+    value.parse().map_err(|_| format!("{} is not a valid {}", value, name))
+}
+
+pub fn parse_switch(args: &[&str]) -> Result<bool, String> {
+    match arg(args, 0, "on or off")? {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => Err(format!("expected on or off, got {}", other)),
+    }
+}
+
+#[derive(Resource, Default)]
+struct Console {
+    open: bool,
+    input: String,
+    history: Vec<String>,
+    // how far back up the history the input came from
+    browsing: Option<usize>,
+    output: VecDeque<String>,
+    pending: Vec<String>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.output.push_back(line.into());
+
+        while self.output.len() > OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleOutput;
+
+#[derive(Component)]
+struct ConsolePrompt;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Console>()
+        .init_resource::<ConsoleCommands>()
+        .add_systems(Startup, spawn_console)
+        .add_systems(PreUpdate, read_input.after(InputSystem))
+        .add_systems(Update, (run_pending, show_console).chain())
+        .console_command("help", "lists commands, or explains one", &[], help)
+        .console_command("clear", "empties the console", &[], |world, _| {
+            world.resource_mut::<Console>().output.clear();
+            Ok(String::new())
+        });
+
+        // completing help's argument means knowing every command, so it is filled in once they're all registered
+        app.add_systems(PostStartup, complete_help);
+    }
+}
+
+fn help(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let commands = world.resource::<ConsoleCommands>();
+
+    match args.first() {
+        Some(name) => commands.0.get(*name)
+            .map(|command| format!("{}: {}", name, command.help))
+            .ok_or_else(|| format!("no command called {}", name)),
+        None => Ok(commands.0.keys().cloned().collect::<Vec<_>>().join(" ")),
+    }
+}
+
+fn complete_help(mut commands: ResMut<ConsoleCommands>) {
+    let names: Vec<String> = commands.0.keys().cloned().collect();
+    commands.complete("help", names);
+}
+
+fn common_prefix(words: &[&String]) -> String {
+    let Some(first) = words.first() else {
+        return String::new();
+    };
+
+    let mut prefix = first.as_str();
+
+    for word in words {
+        while !word.starts_with(prefix) {
+            prefix = &prefix[..prefix.len() - 1];
+        }
+    }
+
+    prefix.to_string()
+}
+
+fn complete(console: &mut Console, commands: &ConsoleCommands) {
+    let words: Vec<&str> = console.input.split(' ').collect();
+
+    let (done, partial, candidates): (String, &str, Vec<&String>) = match words.as_slice() {
+        [partial] => (String::new(), partial, commands.0.keys().collect()),
+        [name, partial] => match commands.0.get(*name) {
+            Some(command) => (format!("{} ", name), partial, command.completions.iter().collect()),
+            None => return,
+        },
+        _ => return,
+    };
+
+    let matches: Vec<&String> = candidates.into_iter().filter(|candidate| candidate.starts_with(partial)).collect();
+
+    match matches.as_slice() {
+        [] => {},
+        [only] => console.input = format!("{}{} ", done, only),
+        _ => {
+            console.input = format!("{}{}", done, common_prefix(&matches));
+            let options = matches.iter().map(|option| option.as_str()).collect::<Vec<_>>().join(" ");
+            console.print(options);
+        },
+    }
+}
+
+// runs before the game sees any input, so typing into the console never presses a game button
+fn read_input(
+    mut console: ResMut<Console>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    commands: Res<ConsoleCommands>,
+) {
+    if keys.just_pressed(TOGGLE_KEY) {
+        console.open = !console.open;
+        keys.reset_all();
+        characters.clear();
+        return;
+    }
+
+    if !console.open {
+        characters.clear();
+        return;
+    }
+
+    for character in characters.read() {
+        if !character.char.is_control() && character.char != '`' {
+            console.input.push(character.char);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Tab) {
+        complete(&mut console, &commands);
+    }
+
+    if keys.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let index = console.browsing.map_or(console.history.len() - 1, |index| index.saturating_sub(1));
+        console.browsing = Some(index);
+        console.input = console.history[index].clone();
+    }
+
+    if keys.just_pressed(KeyCode::Down) {
+        if let Some(index) = console.browsing {
+            match console.history.get(index + 1).cloned() {
+                Some(line) => {
+                    console.browsing = Some(index + 1);
+                    console.input = line;
+                },
+                None => {
+                    console.browsing = None;
+                    console.input.clear();
+                },
+            }
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input).trim().to_string();
+        console.browsing = None;
+
+        if !line.is_empty() {
+            if console.history.last() != Some(&line) {
+                console.history.push(line.clone());
+            }
+
+            console.pending.push(line);
+        }
+    }
+
+    keys.reset_all();
+}
+
+fn run_pending(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<Console>().pending);
+
+    for line in pending {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            continue;
+        };
+
+        let run = world.resource::<ConsoleCommands>().0.get(*name).map(|command| command.run.clone());
+
+        let result = match run {
+            Some(run) => run(world, args),
+            None => Err(format!("no command called {}, try help", name)),
+        };
+
+        let mut console = world.resource_mut::<Console>();
+        console.print(format!("> {}", line));
+
+        match result {
+            Ok(message) if message.is_empty() => {},
+            Ok(message) => console.print(message),
+            Err(error) => console.print(format!("error: {}", error)),
+        }
+    }
+}
+
+fn spawn_console(mut commands: Commands, theme: Res<UiTheme>) {
+    let mut root = generic_ui::anchored(FlexDirection::Column, AlignItems::Start, JustifyContent::Start);
+    root.z_index = ZIndex::Global(200);
+    root.visibility = Visibility::Hidden;
+
+    commands
+        .spawn((root, ConsoleRoot))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(theme.spacing)),
+                        ..default()
+                    },
+                    background_color: theme.overlay.into(),
+                    ..default()
+                })
+                .with_children(|panel| {
+                    generic_ui::text(panel, &theme, "", theme.small_text_size * 0.75, theme.text).insert(ConsoleOutput);
+                    generic_ui::text(panel, &theme, "> ", theme.small_text_size * 0.75, theme.focus).insert(ConsolePrompt);
+                });
+        });
+}
+
+fn show_console(
+    console: Res<Console>,
+    mut roots: Query<&mut Visibility, With<ConsoleRoot>>,
+    mut outputs: Query<&mut Text, (With<ConsoleOutput>, Without<ConsolePrompt>)>,
+    mut prompts: Query<&mut Text, (With<ConsolePrompt>, Without<ConsoleOutput>)>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    for mut visibility in &mut roots {
+        *visibility = match console.open {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+    }
+
+    for mut text in &mut outputs {
+        text.sections[0].value = console.output.iter().cloned().collect::<Vec<_>>().join("\n");
+    }
+
+    for mut text in &mut prompts {
+        text.sections[0].value = format!("> {}_", console.input);
+    }
+}
@@ -0,0 +1,204 @@
+use bevy::{diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin}, prelude::*};
+use crate::app_state::AppState;
+use crate::game::{BadThing, CircleCollider, Difficulty, GoodThing, Mallet, RunClock, Score, ScoreTimer};
+use crate::generic_ui;
+use crate::hazards::{Dormant, PeriodicActiveWindow};
+use crate::pause::Paused;
+use crate::theme::UiTheme;
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+const FREEZE_KEY: KeyCode = KeyCode::F4;
+const STEP_KEY: KeyCode = KeyCode::F5;
+const WINDOW_BAR_WIDTH: f32 = 120.;
+
+#[derive(Resource, Default)]
+struct DebugOverlay {
+    shown: bool,
+    frozen: bool,
+    // unfrozen for a single frame
+    stepping: bool,
+}
+
+#[derive(Component)]
+struct DebugRoot;
+
+#[derive(Component)]
+struct DebugText;
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+
+        app
+        .init_resource::<DebugOverlay>()
+        .add_systems(Startup, spawn_text)
+        .add_systems(Update, (end_step, handle_keys).chain())
+        .add_systems(PostUpdate, hold_freeze)
+        .add_systems(Update, (
+            show_text,
+            (draw_colliders, draw_mallet_windows).run_if(in_state(AppState::Game)),
+        )
+            .after(handle_keys)
+            .run_if(|overlay: Res<DebugOverlay>| overlay.shown)
+        );
+    }
+}
+
+// freezing pauses virtual time, so everything driven by it stops exactly where it is
+fn handle_keys(
+    keys: Res<Input<KeyCode>>,
+    paused: Res<Paused>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut time: ResMut<Time<Virtual>>,
+    mut roots: Query<&mut Visibility, With<DebugRoot>>,
+) {
+    if keys.just_pressed(TOGGLE_KEY) {
+        overlay.shown = !overlay.shown;
+
+        for mut visibility in &mut roots {
+            *visibility = match overlay.shown {
+                true => Visibility::Inherited,
+                false => Visibility::Hidden,
+            };
+        }
+    }
+
+    if keys.just_pressed(FREEZE_KEY) {
+        overlay.frozen = !overlay.frozen;
+
+        // thawing under the pause menu leaves the clock to the menu
+        match overlay.frozen {
+            true => time.pause(),
+            false if !paused.0 => time.unpause(),
+            false => {},
+        }
+    }
+
+    if overlay.frozen && keys.just_pressed(STEP_KEY) {
+        overlay.stepping = true;
+        time.unpause();
+    }
+}
+
+fn end_step(mut overlay: ResMut<DebugOverlay>, mut time: ResMut<Time<Virtual>>) {
+    if overlay.stepping {
+        overlay.stepping = false;
+        time.pause();
+    }
+}
+
+// the pause menu shares the clock and unpauses it whenever it closes, so the freeze is put back after it
+fn hold_freeze(overlay: Res<DebugOverlay>, mut time: ResMut<Time<Virtual>>) {
+    if overlay.frozen && !overlay.stepping && !time.is_paused() {
+        time.pause();
+    }
+}
+
+fn spawn_text(mut commands: Commands, theme: Res<UiTheme>) {
+    let mut root = generic_ui::anchored(FlexDirection::Column, AlignItems::Center, JustifyContent::End);
+    root.z_index = ZIndex::Global(100);
+    root.visibility = Visibility::Hidden;
+
+    commands
+        .spawn((root, DebugRoot))
+        .with_children(|parent| {
+            generic_ui::text(parent, &theme, "", theme.small_text_size * 0.75, theme.focus).insert(DebugText);
+        });
+}
+
+fn show_text(
+    mut texts: Query<&mut Text, With<DebugText>>,
+    overlay: Res<DebugOverlay>,
+    diagnostics: Res<DiagnosticsStore>,
+    difficulty: Option<Res<Difficulty>>,
+    score: Res<Score>,
+    timer: Res<ScoreTimer>,
+    clock: Res<RunClock>,
+) {
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .unwrap_or_default();
+
+    let mut lines = vec![format!("fps {:.0}  clock {:.2}s", fps, clock.0.elapsed_secs())];
+
+    if let Some(difficulty) = difficulty {
+        lines.push(format!("difficulty baby {:.2} sloth {:.2} kitten {:.2}", difficulty.baby, difficulty.sloth, difficulty.kitten));
+    }
+
+    lines.push(format!("score {}  next tick {:.0}%", score.0, timer.0.percent() * 100.));
+
+    if overlay.frozen {
+        lines.push(format!("frozen, {:?} steps a frame", STEP_KEY).to_lowercase());
+    }
+
+    let value = lines.join("\n");
+
+    for mut text in &mut texts {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+// world space, after propagation, the same positions the collision check compares
+#[allow(clippy::type_complexity)]
+fn draw_colliders(
+    mut gizmos: Gizmos,
+    colliders: Query<(&CircleCollider, &GlobalTransform, Has<GoodThing>, Has<BadThing>, Has<Dormant>)>,
+    theme: Res<UiTheme>,
+) {
+    for (circle, transform, good, bad, dormant) in &colliders {
+        let colour = match (good, bad, dormant) {
+            (_, _, true) => theme.text,
+            (true, _, _) => Color::GREEN,
+            (_, true, _) => theme.danger,
+            _ => theme.focus,
+        };
+
+        let radius = circle.radius * transform.compute_transform().scale.x;
+        let position = transform.translation().xy();
+
+        gizmos.circle_2d(position, radius, colour);
+        gizmos.line_2d(position - Vec2::X * 4., position + Vec2::X * 4., colour);
+        gizmos.line_2d(position - Vec2::Y * 4., position + Vec2::Y * 4., colour);
+    }
+}
+
+// a bar under each mallet: the whole period, its active stretch, and where in it we are
+fn draw_mallet_windows(
+    mut gizmos: Gizmos,
+    mallets: Query<(&PeriodicActiveWindow, &GlobalTransform, Has<Dormant>), With<Mallet>>,
+    clock: Res<RunClock>,
+    theme: Res<UiTheme>,
+) {
+    let now = clock.0.elapsed_secs();
+
+    for (window, transform, dormant) in &mallets {
+        let start = transform.translation().xy() - Vec2::new(WINDOW_BAR_WIDTH / 2., 80.);
+        let at = |fraction: f32| start + Vec2::X * WINDOW_BAR_WIDTH * fraction;
+
+        gizmos.line_2d(at(0.), at(1.), theme.text);
+
+        let active_colour = match dormant {
+            true => theme.accent,
+            false => theme.danger,
+        };
+
+        // a window that wraps past the end of the period is drawn as two pieces
+        match window.from <= window.to {
+            true => gizmos.line_2d(at(window.from), at(window.to), active_colour),
+            false => {
+                gizmos.line_2d(at(window.from), at(1.), active_colour);
+                gizmos.line_2d(at(0.), at(window.to), active_colour);
+            },
+        }
+
+        let fraction = ((now + window.phase) / window.period).rem_euclid(1.);
+        gizmos.line_2d(at(fraction) - Vec2::Y * 8., at(fraction) + Vec2::Y * 8., theme.focus);
+    }
+}
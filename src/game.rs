@@ -1,115 +1,509 @@
-use std::ops::{Add, Mul};
-
-use bevy::prelude::*;
-use rand::Rng;
+use bevy::{ecs::system::{EntityCommands, SystemId, SystemParam}, prelude::*, time::Stopwatch, transform::TransformSystem, utils::{HashMap, HashSet}};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use crate::app_state::{self, AppState};
-use crate::generic_ui::{spawn_button, ButtonActionType};
-use crate::{startup, util as util};
+use crate::broadphase::HazardGrid;
+use crate::generic_ui::{self, spawn_button, with_cooldown, ButtonActionType, Cooldown, PressedButton};
+use crate::stamina::Stamina;
+use crate::theme::UiTheme;
+use crate::launch::LaunchConfig;
+use crate::milestones::{Condition, MilestoneExt};
+use crate::hazards::{ActivateSound, Channel, Dormant, Grow, HazardSet, Oscillate, PeriodicActiveWindow, Rotate, Wave};
+use crate::pause;
+use crate::players::{GameMode, Hotkey, Owner, Player, PlayerScores, Roster};
+use crate::motion::{FallAndWrap, Impulse, InvertVelocity, LinearDrift, MotionSet, RescuedBy, ResetTo};
+use crate::util as util;
 
 pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        let launch = app.world.get_resource_or_insert_with(LaunchConfig::default).clone();
+
+        if let Some(mode) = launch.mode {
+            app.insert_resource(mode);
+        }
+
         app
         .insert_resource(Score(0, "".into()))
         .insert_resource(ScoreTimer(Timer::from_seconds(1.0, TimerMode::Repeating)))
-        .add_systems(OnEnter(AppState::Game), (setup_game, spawn_baby_fire).chain())
+        .insert_resource(launch.difficulty_preset.unwrap_or_default())
+        .init_resource::<RunClock>()
+        .init_resource::<RunSeed>()
+        .init_resource::<QueuedSeed>()
+        .init_resource::<GodMode>()
+        .insert_resource(GameRng(ChaCha8Rng::from_entropy()))
+        .register_type::<GoodThing>()
+        .register_type::<Carousel>()
+        .register_type::<Difficulty>()
+        .register_type::<Score>()
+        .register_type::<ScoreTimer>()
+        .add_event::<Rescue>()
+        .add_event::<SituationUnlocked>()
+        .add_event::<Collision>()
+        .add_event::<RunEnded>()
+        .add_event::<SituationRetired>()
+        .init_resource::<Retired>()
+        .init_resource::<BabyActions>()
+        .init_resource::<KittenActions>()
+        .init_resource::<SlothActions>()
+        .init_resource::<ExtraMessages>()
+        .add_systems(OnEnter(AppState::Game), setup_game.in_set(GameSetup))
         .add_systems(OnExit(AppState::Game), util::cleanup_system::<GameEntity>)
         .add_systems(Update, (
-            update_good_thing, 
-            swing_mallet,
-            spin_carousel,
-            good_thing_does_not_touch_bad_thing, score_ticker
+            tick_clock,
+            retire_situations,
+            score_ticker,
+            scale_cooldowns,
         )
             .chain()
+            .after(HazardSet)
+            .after(MotionSet)
             .run_if(in_state(AppState::Game))
-        );
+            .run_if(pause::running)
+        )
+        // colliders are compared in world space, so this waits for transforms to propagate
+        .add_systems(PostUpdate, good_thing_does_not_touch_bad_thing
+            .in_set(CollisionSet)
+            .after(TransformSystem::TransformPropagate)
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        )
+        // declaration order breaks ties on the hud's next unlock
+        .milestone("baby_fire", Condition::Difficulty(Situation::Baby, unlock_threshold(Situation::Baby)), spawn_baby_fire)
+        .milestone("sloth_mallet", Condition::Difficulty(Situation::Sloth, unlock_threshold(Situation::Sloth)), unlock_sloth_mallet)
+        .milestone("second_sloth", Condition::Difficulty(Situation::Sloth, 1.5), unlock_second_sloth)
+        .milestone("kitten_carousel", Condition::Difficulty(Situation::Kitten, unlock_threshold(Situation::Kitten)), unlock_kitten_carousel);
+
+        #[cfg(feature = "debug")]
+        console_commands(app);
+    }
+}
+
+#[cfg(feature = "debug")]
+fn console_commands(app: &mut App) {
+    use crate::console::{self, ConsoleExt};
+    use crate::milestones::{self, Milestones};
 
+    fn in_game(world: &World) -> Result<(), String> {
+        match world.resource::<State<AppState>>().get() {
+            AppState::Game => Ok(()),
+            _ => Err("only works during a game".into()),
+        }
     }
+
+    app
+    .console_command("spawn", "spawn baby|kitten|sloth, bringing its difficulty up to where it unlocks", &["baby", "kitten", "sloth"], |world, args| {
+        in_game(world)?;
+
+        let situation = match console::arg(args, 0, "situation")? {
+            "baby" => Situation::Baby,
+            "kitten" => Situation::Kitten,
+            "sloth" => Situation::Sloth,
+            other => return Err(format!("no situation called {}", other)),
+        };
+
+        let threshold = unlock_threshold(situation);
+        let mut difficulty = world.resource_mut::<Difficulty>();
+
+        match situation {
+            Situation::Baby => difficulty.baby = difficulty.baby.max(threshold),
+            Situation::Kitten => difficulty.kitten = difficulty.kitten.max(threshold),
+            Situation::Sloth => difficulty.sloth = difficulty.sloth.max(threshold),
+        }
+
+        // whatever unlocks the situation, run again even if the run already has it
+        let names: Vec<String> = world.resource::<Milestones>().0.iter()
+            .filter(|milestone| matches!(milestone.condition, Condition::Difficulty(track, target) if track == situation && target <= threshold))
+            .map(|milestone| milestone.name.clone())
+            .collect();
+
+        for name in names {
+            milestones::reach(world, &name)?;
+        }
+
+        Ok(format!("spawned {:?}", situation).to_lowercase())
+    })
+    .console_command("set", "set difficulty.baby|sloth|kitten or score to a number", &["difficulty.baby", "difficulty.sloth", "difficulty.kitten", "score"], |world, args| {
+        in_game(world)?;
+
+        let name = console::arg(args, 0, "setting")?;
+        let value: f32 = console::parse_arg(args, 1, "number")?;
+
+        match name {
+            "difficulty.baby" => world.resource_mut::<Difficulty>().baby = value,
+            "difficulty.sloth" => world.resource_mut::<Difficulty>().sloth = value,
+            "difficulty.kitten" => world.resource_mut::<Difficulty>().kitten = value,
+            "score" => world.resource_mut::<Score>().0 = value as i32,
+            _ => return Err(format!("can't set {}", name)),
+        }
+
+        Ok(format!("{} = {}", name, value))
+    })
+    .console_command("godmode", "godmode on|off, ignoring every collision", &["on", "off"], |world, args| {
+        let on = console::parse_switch(args)?;
+        world.resource_mut::<GodMode>().0 = on;
+        Ok(format!("godmode {}", args[0]))
+    })
+    .console_command("seed", "reseed the current run, and every run after it", &[], |world, args| {
+        let seed: u64 = console::parse_arg(args, 0, "seed")?;
+
+        world.resource_mut::<RunSeed>().0 = seed;
+        world.resource_mut::<GameRng>().0 = ChaCha8Rng::seed_from_u64(seed);
+        world.resource_mut::<LaunchConfig>().seed = Some(seed);
+
+        Ok(format!("seed {}", seed))
+    });
 }
 
-#[derive(Component)]
-struct GameEntity;
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameSetup;
 
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CollisionSet;
 
 #[derive(Component)]
+pub(crate) struct GameEntity;
+
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct GoodThing{
     pub situation: Situation
 }
 
+// the difficulty a situation has to reach before its buttons are on screen
+pub fn unlock_threshold(situation: Situation) -> f32 {
+    match situation {
+        Situation::Baby => 0.,
+        Situation::Sloth => 0.5,
+        Situation::Kitten => 2.,
+    }
+}
+
+// extra game over lines for a situation, on top of the built in one
+#[derive(Resource, Default)]
+pub struct ExtraMessages(pub HashMap<Situation, Vec<String>>);
+
+// a good thing carrying its own game over line
+#[derive(Component)]
+pub struct DeathMessage(pub String);
+
+fn pick_message(situation: &Situation, extra: &ExtraMessages, rng: &mut GameRng) -> String {
+    let Some(lines) = extra.0.get(situation).filter(|lines| !lines.is_empty()) else {
+        return get_message(situation);
+    };
+
+    match rng.0.gen_range(0..=lines.len()) {
+        0 => get_message(situation),
+        index => lines[index - 1].clone(),
+    }
+}
+
 fn get_message(situation: &Situation) -> String {
     match situation {
-        Situation::Baby => return "your negligent inaction led to withered death".to_string(),
-        Situation::Kitten => return "their soft paws were torn to shreds by rusty nails. weeping, they wondered why their protector had abandoned them".to_string(),
-        Situation::Sloth => return "the baby sloth just wanted to eat some leaves - it did not choose to be put in the hands of an incompetent carer".to_string(),
+        Situation::Baby => "your negligent inaction led to withered death".to_string(),
+        Situation::Kitten => "their soft paws were torn to shreds by rusty nails. weeping, they wondered why their protector had abandoned them".to_string(),
+        Situation::Sloth => "the baby sloth just wanted to eat some leaves - it did not choose to be put in the hands of an incompetent carer".to_string(),
     }
 }
 
 #[derive(Component)]
-struct BadThing;
+pub struct BadThing;
 
 #[derive(Component)]
-struct CircleCollider{
-    radius: f32
+pub struct CircleCollider{
+    pub radius: f32
 }
 
-#[derive(Resource)]
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
 pub struct Score(pub i32, pub String);
 
 
-#[derive(Resource)]
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Default)]
+#[reflect(Resource)]
 pub struct Difficulty{
-    baby: f32,
-    sloth: f32,
-    kitten: f32
+    pub baby: f32,
+    pub sloth: f32,
+    pub kitten: f32
+}
+
+impl Difficulty {
+    pub fn of(&self, situation: Situation) -> f32 {
+        match situation {
+            Situation::Baby => self.baby,
+            Situation::Kitten => self.kitten,
+            Situation::Sloth => self.sloth,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyProfile {
+    pub name: String,
+    pub baby: f32,
+    pub tick_seconds: f32,
+}
+
+impl Default for DifficultyProfile {
+    fn default() -> Self {
+        DifficultyProfile {
+            name: "standard".into(),
+            baby: 1.,
+            tick_seconds: 1.,
+        }
+    }
+}
+
+impl DifficultyProfile {
+    pub fn presets() -> Vec<DifficultyProfile> {
+        vec![
+            DifficultyProfile { name: "gentle".into(), baby: 0.5, tick_seconds: 1.5 },
+            DifficultyProfile::default(),
+            DifficultyProfile { name: "brisk".into(), baby: 1., tick_seconds: 0.75 },
+            DifficultyProfile { name: "frantic".into(), baby: 1.5, tick_seconds: 0.5 },
+        ]
+    }
+
+    pub fn preset(name: &str) -> Option<DifficultyProfile> {
+        DifficultyProfile::presets().into_iter().find(|profile| profile.name == name)
+    }
 }
 
-fn setup_game(mut commands: Commands, mut background_colour: ResMut<ClearColor>) {
-    background_colour.0 = startup::CLEAR_COLOUR;
+#[allow(clippy::too_many_arguments)]
+fn setup_game(
+    mut commands: Commands,
+    mut background_colour: ResMut<ClearColor>,
+    mode: Res<GameMode>,
+    mut roster: ResMut<Roster>,
+    mut player_scores: ResMut<PlayerScores>,
+    profile: Res<DifficultyProfile>,
+    mut clock: ResMut<RunClock>,
+    mut rng: ResMut<GameRng>,
+    mut seed: ResMut<RunSeed>,
+    mut queued: ResMut<QueuedSeed>,
+    launch: Res<LaunchConfig>,
+) {
+    background_colour.0 = launch.clear_colour;
+
+    clock.0.reset();
+    // every run gets a seed of its own unless one was asked for, so any run can be replayed
+    seed.0 = queued.0.take().or(launch.seed).unwrap_or_else(rand::random);
+    rng.0 = ChaCha8Rng::seed_from_u64(seed.0);
 
     commands.insert_resource(Score(0, "".to_string()));
+    commands.insert_resource(ScoreTimer(Timer::from_seconds(profile.tick_seconds, TimerMode::Repeating)));
+
+    commands.insert_resource(Retired::default());
+
+    *roster = Roster::for_mode(*mode);
+    *player_scores = PlayerScores::default();
 
     commands.insert_resource(Difficulty {
-        baby: 1.,
+        baby: profile.baby,
         sloth: 0.,
         kitten: 0.
     });
 }
 
 
-
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Reflect, Default)]
+#[serde(rename_all = "snake_case")]
 pub enum Situation {
+    #[default]
     Baby,
     Kitten,
     Sloth,
 }
 
-fn spawn_baby_fire(mut commands: Commands, asset_server: Res<AssetServer>){
-    let node = NodeBundle {
-        style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            display: Display::Flex,
-            flex_direction: FlexDirection::Column,
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
-            ..default()
-        },
-        ..default()
-    };
+#[derive(Event)]
+pub struct Rescue {
+    pub situation: Situation
+}
+
+#[derive(Event)]
+pub struct SituationUnlocked(pub Situation);
+
+// ended from its own button while something else is still in play, it leaves the run rather than ending it
+#[derive(Event)]
+pub struct SituationRetired(pub Situation);
+
+#[derive(Resource, Default)]
+pub struct Retired(pub HashSet<Situation>);
+
+// on every button belonging to a situation, so they go with it when it's retired
+#[derive(Component)]
+pub struct SituationButton(pub Situation);
+
+#[derive(Event)]
+pub struct Collision {
+    pub situation: Situation,
+    pub good: Vec2,
+    pub bad: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndCause {
+    Collision,
+    GaveUp,
+    TimeLimit,
+    // left without a game over, through save & quit or the console
+    Abandoned,
+}
+
+#[derive(Event)]
+pub struct RunEnded {
+    pub situation: Option<Situation>,
+    pub cause: EndCause,
+}
+
+#[derive(SystemParam)]
+pub(crate) struct SituationSpawner<'w> {
+    pub asset_server: Res<'w, AssetServer>,
+    pub roster: Res<'w, Roster>,
+    theme: Res<'w, UiTheme>,
+    baby: Res<'w, BabyActions>,
+    kitten: Res<'w, KittenActions>,
+    sloth: Res<'w, SlothActions>,
+}
+
+fn spawn_situation_button<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, spawner: &SituationSpawner, text: &str, situation: Situation, key: fn(&Player, Situation) -> KeyCode, system: SystemId) -> EntityCommands<'w, 's, 'a> {
+    let owner = spawner.roster.owner(situation);
+    let key = key(&owner, situation);
+
+    let mut button = spawn_button(parent, &spawner.theme, &format!("{}\n[{:?}]", text, key), ButtonActionType::Run(system));
+    button.insert((Hotkey(key), SituationButton(situation)));
+
+    if spawner.roster.mode == GameMode::Coop {
+        button.insert(Owner(owner));
+    }
+
+    button
+}
+
+// each extra point of a situation's difficulty adds this much of the base cooldown
+const COOLDOWN_PER_DIFFICULTY: f32 = 0.5;
+
+#[derive(Component)]
+pub struct RescueCooldown {
+    pub situation: Situation,
+    pub seconds: f32,
+}
+
+fn spawn_rescue_button(parent: &mut ChildBuilder, spawner: &SituationSpawner, text: &str, situation: Situation, system: SystemId, seconds: f32, charges: u32) {
+    let mut button = spawn_situation_button(parent, spawner, text, situation, Player::rescue_key, system);
+    button.insert(RescueCooldown { situation, seconds });
+    with_cooldown(&mut button, &spawner.theme, Cooldown::new(seconds, charges));
+}
+
+fn scale_cooldowns(mut buttons: Query<(&RescueCooldown, &mut Cooldown)>, difficulty: Res<Difficulty>) {
+    for (rescue, mut cooldown) in &mut buttons {
+        let seconds = rescue.seconds * (1. + difficulty.of(rescue.situation) * COOLDOWN_PER_DIFFICULTY);
+
+        if cooldown.seconds != seconds {
+            cooldown.seconds = seconds;
+        }
+    }
+}
+
+// rescues draw on the shared stamina pool, a press that can't be paid for hands its cooldown charge back
+#[derive(SystemParam)]
+struct RescueAttempt<'w, 's> {
+    rescues: EventWriter<'w, Rescue>,
+    stamina: ResMut<'w, Stamina>,
+    pressed: Res<'w, PressedButton>,
+    cooldowns: Query<'w, 's, &'static mut Cooldown>,
+}
+
+impl RescueAttempt<'_, '_> {
+    fn rescue(&mut self, situation: Situation) {
+        if self.stamina.spend(situation) {
+            self.rescues.send(Rescue { situation });
+            return;
+        }
+
+        if let Some(mut cooldown) = self.pressed.entity.and_then(|entity| self.cooldowns.get_mut(entity).ok()) {
+            cooldown.refund();
+        }
+    }
+}
+
+// ending a situation retires it while anything else is left to look after, only the last one ends the run
+#[derive(SystemParam)]
+struct GiveUp<'w, 's> {
+    next_state: ResMut<'w, NextState<AppState>>,
+    score: ResMut<'w, Score>,
+    player_scores: ResMut<'w, PlayerScores>,
+    pressed: Res<'w, PressedButton>,
+    run_ended: EventWriter<'w, RunEnded>,
+    retired: Res<'w, Retired>,
+    retirements: EventWriter<'w, SituationRetired>,
+    good_things: Query<'w, 's, &'static GoodThing>,
+}
+
+impl GiveUp<'_, '_> {
+    fn end(&mut self, situation: Situation, message: &str) {
+        let others = self.good_things.iter()
+            .any(|good_thing| good_thing.situation != situation && !self.retired.0.contains(&good_thing.situation));
+
+        if others {
+            self.retirements.send(SituationRetired(situation));
+            return;
+        }
+
+        self.next_state.set(AppState::GameOver);
+        self.score.1 = message.into();
+        self.player_scores.blamed = self.pressed.owner;
+        self.run_ended.send(RunEnded { situation: Some(situation), cause: EndCause::GaveUp });
+    }
+}
+
+#[derive(Resource)]
+struct BabyActions {
+    pull: SystemId,
+    end: SystemId,
+}
+
+impl FromWorld for BabyActions {
+    fn from_world(world: &mut World) -> Self {
+        BabyActions {
+            pull: world.register_system(pull_baby),
+            end: world.register_system(end_baby),
+        }
+    }
+}
+
+fn pull_baby(mut attempt: RescueAttempt) {
+    attempt.rescue(Situation::Baby);
+}
+
+fn end_baby(mut give_up: GiveUp) {
+    give_up.end(Situation::Baby, "the baby explored, naive and innocent, its newfound territory. you actively steered creation to its death");
+}
+
+pub(crate) fn spawn_baby_fire(mut commands: Commands, spawner: SituationSpawner){
+    let asset_server = &spawner.asset_server;
+
+    let node = generic_ui::screen();
 
     commands
         .spawn((node, GameEntity))
         .with_children(|parent| {
-            spawn_button(parent, &asset_server, "end baby", ButtonActionType::EndGame("the baby explored, naive and innocent, its newfound territory. you actively steered creation to its death".to_string()));
+            spawn_situation_button(parent, &spawner, "end baby", Situation::Baby, Player::end_key, spawner.baby.end);
 
-            spawn_button(parent, &asset_server, "pull baby", ButtonActionType::ProlongInevitable(Situation::Baby));
+            spawn_rescue_button(parent, &spawner, "pull baby", Situation::Baby, spawner.baby.pull, 1.5, 2);
         });
 
     commands.spawn((
-        util::image(Vec2::new(-450., 220.), "baby.png".into(), &asset_server),
+        util::image(Vec2::new(-450., 220.), "baby.png".into(), asset_server),
         GameEntity,
         GoodThing { situation: Situation::Baby },
         CircleCollider{ radius:100. },
+        LinearDrift { velocity: Vec2::new(10., 0.), scale_by_difficulty: Some(Situation::Baby) },
+        RescuedBy(Situation::Baby),
+        ResetTo { x: Some(-450.), y: None },
         AudioBundle {
             source: asset_server.load("baby.wav"),
             settings: PlaybackSettings {
@@ -119,8 +513,9 @@ fn spawn_baby_fire(mut commands: Commands, asset_server: Res<AssetServer>){
         }
     ));
 
+    // the flicker is on the sprite alone, the collider's size comes from the parent's scale
     commands.spawn((
-        util::image_lift(Vec2::new(350., 220.), "fire.png".into(), &asset_server),
+        SpatialBundle::from_transform(Transform::from_xyz(350., 220., 1.)),
         GameEntity,
         BadThing,
         CircleCollider{ radius:100. },
@@ -131,58 +526,62 @@ fn spawn_baby_fire(mut commands: Commands, asset_server: Res<AssetServer>){
                 ..default()
             }
         }
-    ));
+    )).with_children(|parent| {
+        parent.spawn((
+            SpriteBundle { texture: asset_server.load("fire.png"), ..default() },
+            Grow { from: 1., to: 1.06, seconds: 0.4, phase: 0., ping_pong: true },
+        ));
+    });
 }
 
-#[derive(Component)]
-pub struct Carousel(pub f32);
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Carousel;
 
 #[derive(Component)]
 pub struct Nail;
 
-fn spin_carousel
-(
-    mut query: Query<
-    (&mut Transform, &Carousel),
-    Without<Nail>
-    >,
-    mut query2: Query<
-    (&mut Transform, &Carousel),
-    With<Nail>
-    >,
-    time: Res<Time>,
-    difficulty: Res<Difficulty>,
-)
-{
-    for (mut transform, carousel) in &mut query {
-        let angle = transform.rotation.to_euler(EulerRot::XYZ).2 + (carousel.0 * 0.1 * difficulty.kitten * time.delta_seconds());
-        
-        transform.rotation = Quat::from_axis_angle(Vec3::Z, angle);
-    }
+pub const CAROUSEL_CENTRE: Vec2 = Vec2::new(316., -180.);
+pub const CAROUSEL_SPEED: f32 = 0.1;
 
-    for (mut transform, carousel) in &mut query2 {
-        let angle = transform.rotation.to_euler(EulerRot::XYZ).2 + (carousel.0 * 0.1 * difficulty.kitten * time.delta_seconds());
-        
-        transform.rotation = Quat::from_axis_angle(Vec3::Z, angle);
+#[derive(Resource)]
+struct KittenActions {
+    reverse: SystemId,
+}
+
+impl FromWorld for KittenActions {
+    fn from_world(world: &mut World) -> Self {
+        KittenActions {
+            reverse: world.register_system(reverse_world),
+        }
     }
 }
 
-fn spawn_kitten_nail(commands: &mut Commands, asset_server: &Res<AssetServer>){
+fn reverse_world(mut attempt: RescueAttempt) {
+    attempt.rescue(Situation::Kitten);
+}
+
+pub(crate) fn spawn_kitten_nail(commands: &mut Commands, spawner: &SituationSpawner){
+    let asset_server = &spawner.asset_server;
+
     commands.spawn((
-        util::image_low(Vec2::new(316., -180.), "carousel.png".into(), &asset_server),
+        util::image_low(CAROUSEL_CENTRE, "carousel.png".into(), asset_server),
         GameEntity,
-        Carousel(1.)
+        Carousel,
+        Rotate { speed: CAROUSEL_SPEED, direction: 1., scale_by: Some(Situation::Kitten) },
+        RescuedBy(Situation::Kitten),
+        InvertVelocity
     )).with_children(|parent| {
         parent.spawn((
-            util::image(Vec2::new(0., 125.), "bridge.png".into(), &asset_server),
+            util::image(Vec2::new(0., 125.), "bridge.png".into(), asset_server),
         ));
 
         parent.spawn((
-            util::image_rot(Vec2::new(-91., -72.), "bridge.png".into(), &asset_server, 2.3),
+            util::image_rot(Vec2::new(-91., -72.), "bridge.png".into(), asset_server, 2.3),
         ));
 
         parent.spawn((
-            util::image_rot(Vec2::new(125./1.414, -125./1.414), "nails.png".into(), &asset_server, 2. * 6.28/3.),
+            util::image_rot(Vec2::new(125./1.414, -125./1.414), "nails.png".into(), asset_server, 2. * std::f32::consts::TAU/3.),
             BadThing,
             Nail,
             CircleCollider { radius:50. }
@@ -190,7 +589,7 @@ fn spawn_kitten_nail(commands: &mut Commands, asset_server: &Res<AssetServer>){
     });
 
     commands.spawn((
-        util::image(Vec2::new(316., 30.), "kitten.png".into(), &asset_server),
+        util::image(Vec2::new(316., 30.), "kitten.png".into(), asset_server),
         GameEntity,
         GoodThing { situation: Situation::Kitten },
         CircleCollider{ radius:50. },
@@ -203,32 +602,24 @@ fn spawn_kitten_nail(commands: &mut Commands, asset_server: &Res<AssetServer>){
         }
     ));
 
-    let node = NodeBundle {
-        style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            display: Display::Flex,
-            flex_direction: FlexDirection::Row,
-            align_items: AlignItems::End,
-            justify_content: JustifyContent::End,
-            ..default()
-        },
-        ..default()
-    };
+    let node = generic_ui::anchored(FlexDirection::Row, AlignItems::End, JustifyContent::End);
 
     commands
     .spawn((node, GameEntity))
     .with_children(|parent| {
-        spawn_button(parent, &asset_server, "reverse world", ButtonActionType::ProlongInevitable(Situation::Kitten));
+        spawn_rescue_button(parent, spawner, "reverse world", Situation::Kitten, spawner.kitten.reverse, 2., 1);
     });
 }
 
-fn spawn_sloth(commands: &mut Commands, asset_server: &Res<AssetServer>){
+pub(crate) fn spawn_sloth(commands: &mut Commands, asset_server: &Res<AssetServer>){
     commands.spawn((
-        util::image(Vec2::new(-370., 420.), "sloth.png".into(), &asset_server),
+        util::image(Vec2::new(-370., 420.), "sloth.png".into(), asset_server),
         GameEntity,
         GoodThing { situation: Situation::Sloth },
         CircleCollider{ radius:50. },
+        FallAndWrap { speed: 7., top: 420., bottom: -420. },
+        RescuedBy(Situation::Sloth),
+        Impulse { offset: Vec2::new(0., -200.), seconds: 0.25 },
         AudioBundle {
             source: asset_server.load("sloth.wav"),
             settings: PlaybackSettings {
@@ -240,59 +631,183 @@ fn spawn_sloth(commands: &mut Commands, asset_server: &Res<AssetServer>){
 }
 
 #[derive(Component)]
-struct Mallet;
-
-fn spawn_sloth_mallet(mut commands: &mut Commands, asset_server: &Res<AssetServer>){
-
-    let node = NodeBundle {
-        style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            display: Display::Flex,
-            flex_direction: FlexDirection::Row,
-            align_items: AlignItems::End,
-            justify_content: JustifyContent::Center,
-            ..default()
-        },
-        ..default()
-    };
+pub struct Mallet;
 
-    spawn_sloth(&mut commands, &asset_server);
+pub const MALLET_STRIKE: Vec2 = Vec2::new(-387.2, -283.5);
+pub const MALLET_RADIUS: f32 = 50.;
+
+pub const MALLET_RAISED: f32 = 1.7;
+const MALLET_PERIOD: f32 = 1.5;
+// fraction of the swing either side of the downstroke where the mallet is low enough to hit
+const MALLET_STRIKE_WINDOW: f32 = 0.095;
+
+#[derive(Resource)]
+struct SlothActions {
+    push: SystemId,
+    strangle: SystemId,
+}
+
+impl FromWorld for SlothActions {
+    fn from_world(world: &mut World) -> Self {
+        SlothActions {
+            push: world.register_system(push_sloth),
+            strangle: world.register_system(strangle_sloth),
+        }
+    }
+}
+
+fn push_sloth(mut attempt: RescueAttempt) {
+    attempt.rescue(Situation::Sloth);
+}
+
+fn strangle_sloth(mut give_up: GiveUp) {
+    give_up.end(Situation::Sloth, "the baby sloth, choking for air, sheds a tear and squeaks out cries for the safety of its mother. it does not come.");
+}
+
+pub(crate) fn spawn_sloth_mallet(commands: &mut Commands, spawner: &SituationSpawner){
+    let asset_server = &spawner.asset_server;
+
+    let node = generic_ui::anchored(FlexDirection::Row, AlignItems::End, JustifyContent::Center);
+
+    spawn_sloth(commands, asset_server);
 
     commands
     .spawn((node, GameEntity))
     .with_children(|parent| {
-        spawn_button(parent, &asset_server, "strangle sloth", ButtonActionType::EndGame("the baby sloth, choking for air, sheds a tear and squeaks out cries for the safety of its mother. it does not come.".to_string()));
+        spawn_situation_button(parent, spawner, "strangle sloth", Situation::Sloth, Player::end_key, spawner.sloth.strangle);
 
-        spawn_button(parent, &asset_server, "push sloth", ButtonActionType::ProlongInevitable(Situation::Sloth));
+        spawn_rescue_button(parent, spawner, "push sloth", Situation::Sloth, spawner.sloth.push, 1., 3);
     });
 
     commands.spawn((
-        util::image_low(Vec2::new(-387.2, -283.5), "hit_area.png".into(), &asset_server),
+        util::image_low(MALLET_STRIKE, "hit_area.png".into(), asset_server),
         GameEntity,
     ));
 
     commands.spawn((
-        util::image_lift(Vec2::new(-320., -240.), "mallet.png".into(), &asset_server),
+        util::image_lift(Vec2::new(-320., -240.), "mallet.png".into(), asset_server),
+        GameEntity,
+        Mallet,
+        Oscillate {
+            channel: Channel::Rotation,
+            centre: 0.,
+            amplitude: MALLET_RAISED,
+            period: MALLET_PERIOD,
+            phase: 0.,
+            wave: Wave::Bounce,
+        }
+    ));
+
+    commands.spawn((
+        TransformBundle::from_transform(Transform::from_translation(MALLET_STRIKE.extend(0.))),
         GameEntity,
-        Mallet
+        BadThing,
+        Mallet,
+        CircleCollider { radius: MALLET_RADIUS },
+        PeriodicActiveWindow {
+            period: MALLET_PERIOD,
+            from: 1. - MALLET_STRIKE_WINDOW,
+            to: MALLET_STRIKE_WINDOW,
+            phase: 0.,
+        },
+        Dormant,
+        ActivateSound(asset_server.load("mallet.wav")),
     ));
 }
 
+fn unlock_sloth_mallet(mut commands: Commands, spawner: SituationSpawner, mut unlocks: EventWriter<SituationUnlocked>) {
+    spawn_sloth_mallet(&mut commands, &spawner);
+    unlocks.send(SituationUnlocked(Situation::Sloth));
+}
+
+fn unlock_second_sloth(mut commands: Commands, asset_server: Res<AssetServer>, mut unlocks: EventWriter<SituationUnlocked>) {
+    spawn_sloth(&mut commands, &asset_server);
+    unlocks.send(SituationUnlocked(Situation::Sloth));
+}
+
+fn unlock_kitten_carousel(mut commands: Commands, spawner: SituationSpawner, mut unlocks: EventWriter<SituationUnlocked>) {
+    spawn_kitten_nail(&mut commands, &spawner);
+    unlocks.send(SituationUnlocked(Situation::Kitten));
+}
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct ScoreTimer(pub Timer);
+
+#[derive(Resource, Default)]
+pub struct RunClock(pub Stopwatch);
+
 #[derive(Resource)]
-struct ScoreTimer(Timer);
+pub struct GameRng(pub ChaCha8Rng);
 
-fn score_ticker(
+#[derive(Resource, Default)]
+pub struct RunSeed(pub u64);
+
+// a seed for the next run only, used up when it starts
+#[derive(Resource, Default)]
+pub struct QueuedSeed(pub Option<u64>);
+
+// collisions are ignored, for reaching late-game situations while testing
+#[derive(Resource, Default)]
+pub struct GodMode(pub bool);
+
+fn tick_clock(mut clock: ResMut<RunClock>, time: Res<Time>) {
+    clock.0.tick(time.delta());
+}
+
+// checked every frame, so things unlocking after their situation was retired, or rebuilt by a resumed run, go too
+fn retire_situations(
     mut commands: Commands,
+    mut retirements: EventReader<SituationRetired>,
+    mut retired: ResMut<Retired>,
+    mut stamina: ResMut<Stamina>,
+    good_things: Query<(Entity, &GoodThing)>,
+    buttons: Query<(Entity, &SituationButton)>,
+) {
+    for retirement in retirements.read() {
+        if retired.0.insert(retirement.0) {
+            stamina.refund();
+        }
+    }
+
+    if retired.0.is_empty() {
+        return;
+    }
+
+    let gone = good_things.iter().map(|(entity, good_thing)| (entity, good_thing.situation))
+        .chain(buttons.iter().map(|(entity, button)| (entity, button.0)))
+        .filter(|(_, situation)| retired.0.contains(situation));
+
+    for (entity, _) in gone {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// unlocks follow from the difficulty this raises, as milestones
+#[allow(clippy::too_many_arguments)]
+fn score_ticker(
     mut score: ResMut<Score>,
     mut timer: ResMut<ScoreTimer>,
     time: Res<Time>,
     mut difficulty: ResMut<Difficulty>,
-    asset_server: Res<AssetServer>
+    roster: Res<Roster>,
+    mut player_scores: ResMut<PlayerScores>,
+    good_things: Query<&GoodThing>,
+    mut rng: ResMut<GameRng>,
 ){
     if timer.0.tick(time.delta()).just_finished() {
         score.0 += 1;
-        let num = rand::thread_rng().gen_range(0..3);
+
+        for player in roster.players() {
+            let owned: HashSet<Situation> = good_things.iter()
+                .map(|good_thing| good_thing.situation)
+                .filter(|situation| roster.owner(*situation) == *player)
+                .collect();
+
+            player_scores.points[player.index()] += owned.len() as i32;
+        }
+
+        let num = rng.0.gen_range(0..3);
 
         if num == 0 {
             difficulty.baby += 0.1;
@@ -300,136 +815,64 @@ fn score_ticker(
 
         if num == 1 {
             difficulty.sloth += 0.1;
-
-            if (difficulty.sloth - 0.5).abs() < 0.05 {
-                spawn_sloth_mallet(&mut commands, &asset_server);
-            }
-
-            if (difficulty.sloth - 1.5).abs() < 0.05 {
-                spawn_sloth(&mut commands, &asset_server);
-            }
         }
 
         if num == 2 {
             difficulty.kitten += 0.1;
-
-            if (difficulty.kitten - 2.).abs() < 0.05 {
-                spawn_kitten_nail(&mut commands, &asset_server);
-            }
         }
     }
 }
 
-fn swing_mallet(
-    mut query: Query<
-    &mut Transform,
-    With<Mallet>
-    >,
-    mut commands: Commands,
-    mut mallet_down: Query<
-    Entity,
-    (With<Mallet>, With<CircleCollider>, With<BadThing>)
-    >,
-    time: Res<Time>,
-    asset_server: Res<AssetServer>
-){
-    let angle = (1.7)*(1. - (time.elapsed_seconds() * 2.094).sin().abs());
-    for mut transform in &mut query {
-        transform.rotation = Quat::from_axis_angle(Vec3::Z, angle);
-
-        if angle > 1.2 {
-            if mallet_down.is_empty() == true {
-                commands.spawn((
-                    Transform {
-                        translation: Vec3::new(-387.2, -283.5, 0.),
-                        ..default()
-                    },
-                    GameEntity,
-                    BadThing,
-                    Mallet,
-                    CircleCollider { radius:50. },
-                    AudioBundle {
-                        source: asset_server.load("mallet.wav"),
-                        settings: PlaybackSettings {
-                            mode: bevy::audio::PlaybackMode::Once,
-                            ..default()
-                        }
-                    }
-                ));
-            }
-        }
-        else {
-            for hit in &mut mallet_down {
-                commands.entity(hit).despawn_recursive();
-            }
-        }
-
-    }
-}
+// space between two colliders in world space, zero or less once they touch
+pub fn gap(a: &CircleCollider, a_transform: &GlobalTransform, b: &CircleCollider, b_transform: &GlobalTransform) -> f32 {
+    let reach = a.radius * a_transform.compute_transform().scale.x
+        + b.radius * b_transform.compute_transform().scale.x;
 
-fn update_good_thing(
-    mut query: Query<
-    (&GoodThing, &mut Transform)
-    >, 
-    time: Res<Time>,
-    difficulty: Res<Difficulty>,
-){
-    for (good_thing, mut transform) in &mut query {
-        match good_thing.situation {
-            Situation::Baby => {
-                transform.translation.x += difficulty.baby * 10. * time.delta_seconds();
-            },
-            Situation::Kitten => {
-
-            },
-            Situation::Sloth => {
-                transform.translation.y -= 7. * time.delta_seconds();
-
-                if transform.translation.y < -420. {
-                    transform.translation.y = 420.;
-                }
-            },
-        }
-    }
+    a_transform.translation().xy().distance(b_transform.translation().xy()) - reach
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn good_thing_does_not_touch_bad_thing(
-    mut good_things: Query<
-    (&GoodThing, &CircleCollider, &Transform)
+    good_things: Query<
+    (&GoodThing, &CircleCollider, &GlobalTransform, Option<&DeathMessage>)
     >, 
-    mut bad_things: Query<
-    (&BadThing, &CircleCollider, &Transform, Option<&Nail>)
-    >,
+    hazards: Res<HazardGrid>,
     mut score: ResMut<Score>,
     mut current_game_state: ResMut<NextState<app_state::AppState>>,
-    world_query: Query<(&Transform, &Carousel)>
+    roster: Res<Roster>,
+    mut player_scores: ResMut<PlayerScores>,
+    mut collisions: EventWriter<Collision>,
+    mut run_ended: EventWriter<RunEnded>,
+    extra_messages: Res<ExtraMessages>,
+    mut rng: ResMut<GameRng>,
+    god_mode: Res<GodMode>,
 ){
-    for (good_thing, good_circle, good_transform) in &mut good_things {
-        for (_, bad_circle, bad_transform, maybe_nail) in &mut bad_things {
-            let good_pos = good_transform.translation.xy();
-            let mut bad_pos = bad_transform.translation.xy();
-
-            match maybe_nail {
-                Some(_) => {
-                    for (transform, _) in &world_query {
-                        let angle = transform.rotation;
-
-                        let direction_vector = angle.mul_vec3(bad_transform.translation).xy();
-
-                        bad_pos = Vec2::new(316., -180.).add(direction_vector);
-                    }
-                }
-                None => {}
-            }
-
-            let distance2 = good_pos.distance_squared(bad_pos);
-        
-            if distance2 <= (good_circle.radius + bad_circle.radius)*(good_circle.radius + bad_circle.radius) {
-                current_game_state.set(AppState::GameOver);
-                score.1 = get_message(&good_thing.situation);
-            }
-        }
+    if god_mode.0 {
+        return;
     }
 
+    // one hit ends the run, so only the first is written, however many things touch this frame
+    let hit = good_things.iter().find_map(|(good_thing, good_circle, good_transform, death_message)| {
+        let good_pos = good_transform.translation().xy();
+        let good_radius = good_circle.radius * good_transform.compute_transform().scale.x;
 
-}
\ No newline at end of file
+        // only the hazards sharing a grid cell are looked at, and those returned are already touching
+        hazards.0.overlapping(good_pos, good_radius)
+            .find(|(hazard, _, _)| !hazard.dormant)
+            .map(|(_, bad_pos, _)| (good_thing, death_message, good_pos, bad_pos))
+    });
+
+    let Some((good_thing, death_message, good_pos, bad_pos)) = hit else {
+        return;
+    };
+
+    current_game_state.set(AppState::GameOver);
+    score.1 = match death_message {
+        Some(message) => message.0.clone(),
+        None => pick_message(&good_thing.situation, &extra_messages, &mut rng),
+    };
+    player_scores.blamed = Some(roster.owner(good_thing.situation));
+
+    collisions.send(Collision { situation: good_thing.situation, good: good_pos, bad: bad_pos });
+    run_ended.send(RunEnded { situation: Some(good_thing.situation), cause: EndCause::Collision });
+}
@@ -1,8 +1,11 @@
 use bevy::prelude::*;
 use crate::app_state::AppState;
+use crate::combo::ScoreBreakdown;
 use crate::game as game;
 use crate::util as util;
-use crate::generic_ui::{ ButtonActionType, spawn_button };
+use crate::generic_ui::{ self, ButtonActionType, spawn_button };
+use crate::players::{GameMode, PlayerScores, Roster};
+use crate::theme::UiTheme;
 
 pub struct GameOverPlugin;
 impl Plugin for GameOverPlugin {
@@ -17,62 +20,55 @@ impl Plugin for GameOverPlugin {
 #[derive(Component)]
 struct GameOverEntity;
 
-fn setup_gameover(mut commands: Commands, asset_server: Res<AssetServer>, score: Res<game::Score>, mut background_colour: ResMut<ClearColor>) {
+#[allow(clippy::too_many_arguments)]
+fn setup_gameover(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    score: Res<game::Score>,
+    roster: Res<Roster>,
+    player_scores: Res<PlayerScores>,
+    breakdown: Res<ScoreBreakdown>,
+    theme: Res<UiTheme>,
+    mut background_colour: ResMut<ClearColor>
+) {
 
-    background_colour.0 = Color::rgb(47./255., 31./255., 13./255.);
+    background_colour.0 = theme.game_over_background;
 
-    let node = NodeBundle {
-        style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            display: Display::Flex,
-            flex_direction: FlexDirection::Column,
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
-            ..default()
-        },
-        ..default()
-    };
-
-    let score_text: String = format!("score: {}", score.0.to_string());
+    let score_text: String = format!("score: {}", score.0);
 
     commands
-        .spawn((node, GameOverEntity))
+        .spawn((generic_ui::screen(), GameOverEntity))
         .with_children(|parent| {
-            parent.spawn(
-                TextBundle::from_section(
-                    "you are a failure",
-                    TextStyle {
-                        font: asset_server.load("PoorStory-Regular.ttf"),
-                        font_size: 60.0,
-                        color: Color::rgb(0.9, 0.3, 0.3)
-                    }
-                )
-            );
+            generic_ui::text(parent, &theme, "you are a failure", theme.title_size, theme.danger);
+
+            generic_ui::label(parent, &theme, score.1.to_string());
+
+            generic_ui::label(parent, &theme, score_text);
+
+            if breakdown.near_misses > 0 {
+                generic_ui::text(parent, &theme, format!(
+                    "survival {}    {} near misses +{}    best combo x{:.1}",
+                    breakdown.survival(&score),
+                    breakdown.near_misses,
+                    breakdown.bonus,
+                    breakdown.best_combo,
+                ), theme.small_text_size, theme.text);
+            }
+
+            if roster.mode == GameMode::Coop {
+                let split = roster.players().iter()
+                    .map(|player| format!("{}: {}", player.name(), player_scores.points[player.index()]))
+                    .collect::<Vec<String>>()
+                    .join("    ");
 
-            parent.spawn(
-                TextBundle::from_section(
-                    score.1.to_string(),
-                    TextStyle {
-                        font: asset_server.load("PoorStory-Regular.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
-                    },
-                )
-            );
+                generic_ui::label(parent, &theme, split);
 
-            parent.spawn(
-                TextBundle::from_section(
-                    score_text,
-                    TextStyle {
-                        font: asset_server.load("PoorStory-Regular.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
-                    },
-                )
-            );
+                if let Some(blamed) = player_scores.blamed {
+                    generic_ui::text(parent, &theme, format!("{} let it happen", blamed.name()), theme.text_size, theme.player_colour(blamed, Interaction::Hovered));
+                }
+            }
 
-            spawn_button(parent, &asset_server, "try again", ButtonActionType::ChangeAppState(AppState::Game));
+            spawn_button(parent, &theme, "try again", ButtonActionType::ChangeAppState(AppState::Game));
         });
 
     commands.spawn((util::image(Vec2::new(0., 220.), "logo.png".into(), &asset_server), GameOverEntity));
@@ -1,112 +1,673 @@
-use bevy::prelude::*;
-use crate::{app_state::{self, AppState}, game::{Carousel, GoodThing, Score, Situation}};
+use std::sync::Arc;
 
-const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.35, 0.35);
+use bevy::{ecs::system::{EntityCommands, SystemId}, prelude::*, ui::{FocusPolicy, RelativeCursorPosition}, utils::HashSet};
+use crate::{app_state::{self, AppState}, pause::{Frozen, Paused}, players::{Hotkey, Owner, Player}, theme::UiTheme};
 
+pub struct GenericUiPlugin;
+
+impl Plugin for GenericUiPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<UiTheme>()
+        .init_resource::<PressedButton>()
+        .init_resource::<Focus>()
+        .add_event::<Activated>()
+        .add_systems(Update, (focus_hovered, navigate_focus, show_focus).chain().in_set(FocusSet))
+        .add_systems(Update, (
+            button_interaction_system,
+            (drag_slider, show_slider).chain(),
+            (flip_toggle, show_toggle).chain(),
+            (select_list_item, show_list).chain(),
+            (recharge, show_cooldown).chain(),
+            show_meter,
+        ).after(FocusSet));
+    }
+}
 
 #[derive(Component)]
 pub struct ButtonAction {
     action_type: ButtonActionType
 }
 
+// Run takes a system registered with World::register_system, usually held in a resource
+// next to the code that owns the button
+#[derive(Clone)]
 pub enum ButtonActionType {
     ChangeAppState(AppState),
-    EndGame(String),
-    ProlongInevitable(Situation)
+    Run(SystemId),
+    Callback(Arc<dyn Fn(&mut World) + Send + Sync>),
+}
+
+impl ButtonActionType {
+    pub fn callback(callback: impl Fn(&mut World) + Send + Sync + 'static) -> Self {
+        ButtonActionType::Callback(Arc::new(callback))
+    }
+}
+
+// the button whose action is running, for systems that care who pressed it
+#[derive(Resource, Default)]
+pub struct PressedButton {
+    pub entity: Option<Entity>,
+    pub owner: Option<Player>,
+}
+
+// the button arrow keys and the d-pad move between, every Button can take focus
+#[derive(Resource, Default)]
+pub struct Focus(pub Option<Entity>);
+
+// while one of these is on screen focus stays inside it, so menus under a modal can't be reached
+#[derive(Component)]
+pub struct FocusScope;
+
+// Enter or the A button on the focused widget, handled the same as a click
+#[derive(Event)]
+pub struct Activated(pub Entity);
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FocusSet;
+
+// a button with a cooldown only fires while it has a charge left, charges come back one at a time
+#[derive(Component, Debug, Clone)]
+pub struct Cooldown {
+    pub seconds: f32,
+    pub charges: u32,
+    pub available: u32,
+    elapsed: f32,
+}
+
+impl Cooldown {
+    pub fn new(seconds: f32, charges: u32) -> Self {
+        Cooldown { seconds, charges, available: charges, elapsed: 0. }
+    }
+
+    pub fn take(&mut self) -> bool {
+        match self.available {
+            0 => false,
+            _ => {
+                self.available -= 1;
+                true
+            },
+        }
+    }
+
+    pub fn refund(&mut self) {
+        self.available = (self.available + 1).min(self.charges);
+    }
+
+    // how full the indicator is, whole charges plus progress towards the next one
+    pub fn fill(&self) -> f32 {
+        ((self.available as f32 + self.elapsed / self.seconds) / self.charges.max(1) as f32).clamp(0., 1.)
+    }
+}
+
+#[derive(Component)]
+struct CooldownFill;
+
+// a read-only bar, fill is 0 to 1
+#[derive(Component, Debug, Clone)]
+pub struct Meter {
+    pub value: f32,
+}
+
+#[derive(Component)]
+struct MeterFill;
+
+#[derive(Component, Debug, Clone)]
+pub struct Slider {
+    pub value: f32,
+}
+
+#[derive(Component)]
+struct SliderFill;
+
+#[derive(Component, Debug, Clone)]
+pub struct Toggle {
+    pub on: bool,
+    label: String,
+}
+
+#[derive(Component, Debug, Clone, Default)]
+pub struct List {
+    pub selected: Option<usize>,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ListItem(pub usize);
+
+pub fn anchored(flex_direction: FlexDirection, align_items: AlignItems, justify_content: JustifyContent) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            display: Display::Flex,
+            flex_direction,
+            align_items,
+            justify_content,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+// a full-screen column with everything centred, the root of most screens
+pub fn screen() -> NodeBundle {
+    anchored(FlexDirection::Column, AlignItems::Center, JustifyContent::Center)
+}
+
+pub fn text<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, text: impl Into<String>, size: f32, color: Color) -> EntityCommands<'w, 's, 'a> {
+    parent.spawn(
+        TextBundle::from_section(text, theme.text_style(size, color))
+            .with_text_alignment(TextAlignment::Center)
+    )
+}
+
+pub fn label<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, text: impl Into<String>) -> EntityCommands<'w, 's, 'a> {
+    self::text(parent, theme, text, theme.text_size, theme.text)
+}
+
+pub fn title<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, text: impl Into<String>) -> EntityCommands<'w, 's, 'a> {
+    self::text(parent, theme, text, theme.title_size, theme.text)
+}
+
+pub fn panel<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme) -> EntityCommands<'w, 's, 'a> {
+    parent.spawn(NodeBundle {
+        style: Style {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            padding: UiRect::all(Val::Px(theme.spacing * 4.)),
+            border: UiRect::all(Val::Px(theme.border_width)),
+            row_gap: Val::Px(theme.spacing),
+            ..default()
+        },
+        border_color: BorderColor(theme.border),
+        background_color: theme.panel.into(),
+        ..default()
+    })
+}
+
+// dims and blocks everything under it, with the content laid out in a panel
+pub fn modal(commands: &mut Commands, theme: &UiTheme, marker: impl Bundle, content: impl FnOnce(&mut ChildBuilder)) -> Entity {
+    let mut overlay = screen();
+    overlay.background_color = theme.overlay.into();
+    overlay.focus_policy = FocusPolicy::Block;
+    overlay.z_index = ZIndex::Global(10);
+
+    commands
+        .spawn((overlay, marker, FocusScope))
+        .with_children(|parent| {
+            panel(parent, theme).with_children(content);
+        })
+        .id()
 }
 
-pub fn spawn_button(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>, text: &str, action_type: ButtonActionType){
-    parent.spawn((ButtonBundle {
+fn button_bundle(theme: &UiTheme, size: Vec2, colour: Color) -> ButtonBundle {
+    ButtonBundle {
         style: Style {
-            width: Val::Px(200.),
-            height: Val::Px(125.),
-            border: UiRect::all(Val::Px(5.)),
-            margin: UiRect::all(Val::Px(5.)),
+            width: Val::Px(size.x),
+            height: Val::Px(size.y),
+            border: UiRect::all(Val::Px(theme.border_width)),
+            margin: UiRect::all(Val::Px(theme.spacing)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
         },
-        border_color: BorderColor(Color::BLACK),
-        background_color: NORMAL_BUTTON.into(),
+        border_color: BorderColor(theme.border),
+        background_color: colour.into(),
         ..default()
-    }, ButtonAction { action_type }))
-    .with_children(|button| {
-        button.spawn(TextBundle::from_section(
-            text,
-            TextStyle {
-                font: asset_server.load("PoorStory-Regular.ttf"),
-                font_size: 40.0,
-                color: Color::rgb(0.9, 0.9, 0.9),
+    }
+}
+
+pub fn spawn_button<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, text: &str, action_type: ButtonActionType) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn((button_bundle(theme, theme.button_size, theme.button), ButtonAction { action_type }));
+
+    button.with_children(|button| {
+        label(button, theme, text);
+    });
+
+    button
+}
+
+pub fn with_cooldown(button: &mut EntityCommands, theme: &UiTheme, cooldown: Cooldown) {
+    button.insert(cooldown).with_children(|button| {
+        button.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(0.),
+                    bottom: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Px(theme.spacing * 2.),
+                    ..default()
+                },
+                background_color: theme.accent.into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            },
+            CooldownFill,
+        ));
+    });
+}
+
+pub fn meter<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, value: f32, colour: Color) -> EntityCommands<'w, 's, 'a> {
+    let mut meter = parent.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Px(theme.slider_size.x),
+                height: Val::Px(theme.slider_size.y),
+                border: UiRect::all(Val::Px(theme.border_width)),
+                margin: UiRect::all(Val::Px(theme.spacing)),
+                justify_content: JustifyContent::Start,
+                align_items: AlignItems::Stretch,
+                ..default()
+            },
+            border_color: BorderColor(theme.border),
+            background_color: theme.button.into(),
+            ..default()
+        },
+        Meter { value: value.clamp(0., 1.) },
+    ));
+
+    meter.with_children(|meter| {
+        meter.spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(value.clamp(0., 1.) * 100.),
+                    ..default()
+                },
+                background_color: colour.into(),
+                ..default()
             },
+            MeterFill,
         ));
     });
+
+    meter
+}
+
+pub fn slider<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, value: f32) -> EntityCommands<'w, 's, 'a> {
+    let mut track = button_bundle(theme, theme.slider_size, theme.button);
+    track.style.justify_content = JustifyContent::Start;
+    track.style.align_items = AlignItems::Stretch;
+
+    let mut slider = parent.spawn((
+        track,
+        RelativeCursorPosition::default(),
+        Slider { value: value.clamp(0., 1.) },
+    ));
+
+    slider.with_children(|slider| {
+        slider.spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(value.clamp(0., 1.) * 100.),
+                    ..default()
+                },
+                background_color: theme.accent.into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            },
+            SliderFill,
+        ));
+    });
+
+    slider
+}
+
+pub fn toggle<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, text: &str, on: bool) -> EntityCommands<'w, 's, 'a> {
+    let mut toggle = parent.spawn((
+        button_bundle(theme, theme.button_size, theme.button),
+        Toggle { on, label: text.into() },
+    ));
+
+    toggle.with_children(|toggle| {
+        label(toggle, theme, "");
+    });
+
+    toggle
+}
+
+pub fn list<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme, items: &[String]) -> EntityCommands<'w, 's, 'a> {
+    let mut list = parent.spawn((
+        NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(theme.spacing),
+                ..default()
+            },
+            ..default()
+        },
+        List::default(),
+    ));
+
+    list.with_children(|list| {
+        for (index, item) in items.iter().enumerate() {
+            let mut row = button_bundle(theme, Vec2::new(theme.button_size.x * 3., theme.row_height), theme.button);
+            row.style.margin = UiRect::ZERO;
+            row.style.justify_content = JustifyContent::Start;
+            row.style.padding = UiRect::horizontal(Val::Px(theme.spacing * 2.));
+
+            list.spawn((row, ListItem(index))).with_children(|row| {
+                self::text(row, theme, item, theme.small_text_size, theme.text);
+            });
+        }
+    });
+
+    list
+}
+
+fn button_colour(theme: &UiTheme, interaction: Interaction, owner: Option<&Owner>) -> Color {
+    match owner {
+        Some(owner) => theme.player_colour(owner.0, interaction),
+        None => theme.button_colour(interaction),
+    }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn button_interaction_system(
+    mut commands: Commands,
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, &ButtonAction),
-        (Changed<Interaction>, With<Button>),
+        (Entity, Ref<Interaction>, &mut BackgroundColor, &ButtonAction, Option<&Owner>, Option<&Hotkey>, Option<&mut Cooldown>),
+        With<Button>,
     >,
+    keys: Res<Input<KeyCode>>,
+    paused: Res<Paused>,
+    frozen: Res<Frozen>,
+    theme: Res<UiTheme>,
+    mut activations: EventReader<Activated>,
     mut current_game_state: ResMut<NextState<app_state::AppState>>,
-    mut score: ResMut<Score>,
-    mut good_things: Query<
-    (&GoodThing, &mut Transform)
-    >,
-    mut carousel: Query<
-    (&mut Carousel)
-    >,
 ) {
-    for (interaction, mut color, button_action) in &mut interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
-                *color = PRESSED_BUTTON.into();
-
-                match &button_action.action_type {
-                    ButtonActionType::ChangeAppState(game_state) => current_game_state.set(*game_state),
-                    ButtonActionType::EndGame(message) => {
-                        current_game_state.set(AppState::GameOver);
-                        score.1 = message.into();
-                    },
-                    ButtonActionType::ProlongInevitable(situation) => {
-                        match situation {
-                            Situation::Baby => {
-                                for (good_thing, mut transform) in &mut good_things {
-                                    match good_thing.situation {
-                                        Situation::Baby => {
-                                            transform.translation.x = -450.;
-                                        }
-                                        _ => {},
-                                    }
-                                }
-                            },
-                            Situation::Kitten => {
-                                for mut c in &mut carousel {
-                                    c.0 = -c.0;
-                                }
-                            },
-                            Situation::Sloth => {
-                                for (good_thing, mut transform) in &mut good_things {
-                                    match good_thing.situation {
-                                        Situation::Sloth => {
-                                            transform.translation.y -= 200.;
-                                        }
-                                        _ => {},
-                                    }
-                                }
-                            }
-                        }
-                    },
+    let activated: HashSet<Entity> = activations.read().map(|activation| activation.0).collect();
+
+    for (entity, interaction, mut color, button_action, owner, hotkey, cooldown) in &mut interaction_query {
+        let hotkey_pressed = !paused.0 && hotkey.is_some_and(|hotkey| keys.just_pressed(hotkey.0));
+        let hotkey_released = hotkey.is_some_and(|hotkey| keys.just_released(hotkey.0));
+        let activated = activated.contains(&entity);
+
+        if !interaction.is_changed() && !hotkey_pressed && !hotkey_released && !activated {
+            continue;
+        }
+
+        let shown = match hotkey.is_some_and(|hotkey| keys.pressed(hotkey.0)) {
+            true => Interaction::Pressed,
+            false => *interaction,
+        };
+        *color = button_colour(&theme, shown, owner).into();
+
+        let clicked = interaction.is_changed() && *interaction == Interaction::Pressed;
+        if !clicked && !hotkey_pressed && !activated {
+            continue;
+        }
+
+        // the run is already decided while the finale plays, nothing pressed during it counts
+        if frozen.0 {
+            continue;
+        }
+
+        if cooldown.is_some_and(|mut cooldown| !cooldown.take()) {
+            continue;
+        }
+
+        commands.insert_resource(PressedButton { entity: Some(entity), owner: owner.map(|owner| owner.0) });
+
+        match &button_action.action_type {
+            ButtonActionType::ChangeAppState(game_state) => current_game_state.set(*game_state),
+            ButtonActionType::Run(system) => commands.run_system(*system),
+            ButtonActionType::Callback(callback) => {
+                let callback = callback.clone();
+                commands.add(move |world: &mut World| callback(world));
+            },
+        }
+    }
+}
+
+fn recharge(mut cooldowns: Query<&mut Cooldown>, time: Res<Time>) {
+    for mut cooldown in &mut cooldowns {
+        if cooldown.available >= cooldown.charges {
+            cooldown.elapsed = 0.;
+            continue;
+        }
+
+        cooldown.elapsed += time.delta_seconds();
+
+        if cooldown.elapsed >= cooldown.seconds {
+            cooldown.elapsed -= cooldown.seconds;
+            cooldown.available += 1;
+        }
+    }
+}
+
+fn show_cooldown(cooldowns: Query<(&Cooldown, &Children)>, mut fills: Query<&mut Style, With<CooldownFill>>) {
+    for (cooldown, children) in &cooldowns {
+        for child in children {
+            let width = Val::Percent(cooldown.fill() * 100.);
+
+            if let Ok(mut style) = fills.get_mut(*child) {
+                if style.width != width {
+                    style.width = width;
                 }
+            }
+        }
+    }
+}
+
+fn drag_slider(mut sliders: Query<(&Interaction, &RelativeCursorPosition, &mut Slider)>) {
+    for (interaction, cursor, mut slider) in &mut sliders {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        if let Some(position) = cursor.normalized {
+            let value = position.x.clamp(0., 1.);
+
+            if value != slider.value {
+                slider.value = value;
+            }
+        }
+    }
+}
+
+fn show_slider(sliders: Query<(&Slider, &Children), Changed<Slider>>, mut fills: Query<&mut Style, With<SliderFill>>) {
+    for (slider, children) in &sliders {
+        for child in children {
+            if let Ok(mut style) = fills.get_mut(*child) {
+                style.width = Val::Percent(slider.value.clamp(0., 1.) * 100.);
+            }
+        }
+    }
+}
+
+fn show_meter(meters: Query<(&Meter, &Children), Changed<Meter>>, mut fills: Query<&mut Style, With<MeterFill>>) {
+    for (meter, children) in &meters {
+        for child in children {
+            if let Ok(mut style) = fills.get_mut(*child) {
+                style.width = Val::Percent(meter.value.clamp(0., 1.) * 100.);
+            }
+        }
+    }
+}
+
+fn flip_toggle(mut toggles: Query<(Entity, Ref<Interaction>, &mut Toggle)>, mut activations: EventReader<Activated>) {
+    let activated: HashSet<Entity> = activations.read().map(|activation| activation.0).collect();
+
+    for (entity, interaction, mut toggle) in &mut toggles {
+        let clicked = interaction.is_changed() && *interaction == Interaction::Pressed;
+
+        if clicked || activated.contains(&entity) {
+            toggle.on = !toggle.on;
+        }
+    }
+}
 
+fn show_toggle(
+    mut toggles: Query<(&Toggle, &Children, &mut BackgroundColor), Changed<Toggle>>,
+    mut texts: Query<&mut Text>,
+    theme: Res<UiTheme>,
+) {
+    for (toggle, children, mut colour) in &mut toggles {
+        *colour = match toggle.on {
+            true => theme.accent,
+            false => theme.button,
+        }.into();
+
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = format!("{}: {}", toggle.label, if toggle.on { "on" } else { "off" });
             }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
+        }
+    }
+}
+
+fn select_list_item(items: Query<(Entity, Ref<Interaction>, &ListItem, &Parent)>, mut lists: Query<&mut List>, mut activations: EventReader<Activated>) {
+    let activated: HashSet<Entity> = activations.read().map(|activation| activation.0).collect();
+
+    for (entity, interaction, item, parent) in &items {
+        let clicked = interaction.is_changed() && *interaction == Interaction::Pressed;
+
+        if !clicked && !activated.contains(&entity) {
+            continue;
+        }
+
+        if let Ok(mut list) = lists.get_mut(parent.get()) {
+            list.selected = Some(item.0);
+        }
+    }
+}
+
+fn show_list(
+    lists: Query<(&List, &Children), Changed<List>>,
+    mut items: Query<(&ListItem, &mut BackgroundColor)>,
+    theme: Res<UiTheme>,
+) {
+    for (list, children) in &lists {
+        for child in children {
+            if let Ok((item, mut colour)) = items.get_mut(*child) {
+                *colour = match list.selected == Some(item.0) {
+                    true => theme.accent,
+                    false => theme.button,
+                }.into();
             }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
+        }
+    }
+}
+
+const NAVIGATION: [(KeyCode, GamepadButtonType, Vec2); 4] = [
+    (KeyCode::Up, GamepadButtonType::DPadUp, Vec2::NEG_Y),
+    (KeyCode::Down, GamepadButtonType::DPadDown, Vec2::Y),
+    (KeyCode::Left, GamepadButtonType::DPadLeft, Vec2::NEG_X),
+    (KeyCode::Right, GamepadButtonType::DPadRight, Vec2::X),
+];
+
+fn just_pressed(keys: &Input<KeyCode>, pads: &Input<GamepadButton>, gamepads: &Gamepads, key: KeyCode, button: GamepadButtonType) -> bool {
+    keys.just_pressed(key) || gamepads.iter().any(|gamepad| pads.just_pressed(GamepadButton::new(gamepad, button)))
+}
+
+fn in_scope(entity: Entity, scope: Option<Entity>, parents: &Query<&Parent>) -> bool {
+    let Some(scope) = scope else {
+        return true;
+    };
+
+    let mut current = entity;
+
+    loop {
+        if current == scope {
+            return true;
+        }
+
+        match parents.get(current) {
+            Ok(parent) => current = parent.get(),
+            Err(_) => return false,
+        }
+    }
+}
+
+fn focus_hovered(mut focus: ResMut<Focus>, buttons: Query<(Entity, &Interaction), Changed<Interaction>>) {
+    for (entity, interaction) in &buttons {
+        if *interaction == Interaction::Hovered && focus.0 != Some(entity) {
+            focus.0 = Some(entity);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn navigate_focus(
+    mut focus: ResMut<Focus>,
+    mut activations: EventWriter<Activated>,
+    keys: Res<Input<KeyCode>>,
+    pads: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    buttons: Query<(Entity, &GlobalTransform), With<Button>>,
+    mut sliders: Query<&mut Slider>,
+    scopes: Query<Entity, With<FocusScope>>,
+    parents: Query<&Parent>,
+) {
+    let scope = scopes.iter().last();
+    let candidates: Vec<(Entity, Vec2)> = buttons.iter()
+        .filter(|(entity, _)| in_scope(*entity, scope, &parents))
+        .map(|(entity, transform)| (entity, transform.translation().xy()))
+        .collect();
+
+    let position = |entity: Entity| candidates.iter().find(|(candidate, _)| *candidate == entity).map(|(_, position)| *position);
+
+    if focus.0.is_some_and(|entity| position(entity).is_none()) {
+        focus.0 = None;
+    }
+
+    let direction = NAVIGATION.iter()
+        .find(|(key, button, _)| just_pressed(&keys, &pads, &gamepads, *key, *button))
+        .map(|(_, _, direction)| *direction);
+
+    if let Some(direction) = direction {
+        match focus.0.and_then(|entity| Some((entity, position(entity)?))) {
+            Some((current, from)) => {
+                // sliders keep left and right for themselves
+                if let Ok(mut slider) = sliders.get_mut(current) {
+                    if direction.y == 0. {
+                        slider.value = (slider.value + direction.x * 0.1).clamp(0., 1.);
+                        return;
+                    }
+                }
+
+                // the nearest button in that direction, favouring ones in line with the current one
+                let next = candidates.iter()
+                    .filter_map(|(entity, position)| {
+                        let offset = *position - from;
+                        let along = offset.dot(direction);
+                        let across = (offset - direction * along).length();
+
+                        (along > 1.).then_some((*entity, along + across * 2.))
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+
+                if let Some((next, _)) = next {
+                    focus.0 = Some(next);
+                }
+            },
+            None => {
+                focus.0 = candidates.iter()
+                    .min_by(|a, b| a.1.y.total_cmp(&b.1.y).then(a.1.x.total_cmp(&b.1.x)))
+                    .map(|(entity, _)| *entity);
             },
         }
     }
-}
\ No newline at end of file
+
+    if just_pressed(&keys, &pads, &gamepads, KeyCode::Return, GamepadButtonType::South) {
+        if let Some(entity) = focus.0 {
+            activations.send(Activated(entity));
+        }
+    }
+}
+
+fn show_focus(focus: Res<Focus>, mut borders: Query<(Entity, &mut BorderColor), With<Button>>, theme: Res<UiTheme>) {
+    if !focus.is_changed() {
+        return;
+    }
+
+    for (entity, mut border) in &mut borders {
+        border.0 = match focus.0 == Some(entity) {
+            true => theme.focus,
+            false => theme.border,
+        };
+    }
+}
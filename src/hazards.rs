@@ -0,0 +1,229 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use crate::app_state::AppState;
+use crate::game::{Difficulty, RunClock, Situation};
+use crate::pause;
+
+// every behaviour is evaluated against the run clock plus its own phase, so hazards restart
+// with each run and land on the same beat after a save is restored
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Rotation,
+    X,
+    Y,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wave {
+    Sine,
+    // 1 at the start of each period, dipping to 0 halfway through
+    Bounce,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Oscillate {
+    pub channel: Channel,
+    pub centre: f32,
+    pub amplitude: f32,
+    pub period: f32,
+    pub phase: f32,
+    pub wave: Wave,
+}
+
+impl Oscillate {
+    pub fn value(&self, time: f32) -> f32 {
+        let cycle = (time + self.phase) / self.period;
+
+        let wave = match self.wave {
+            Wave::Sine => (cycle * TAU).sin(),
+            Wave::Bounce => 1. - (cycle * PI).sin().abs(),
+        };
+
+        self.centre + self.amplitude * wave
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Rotate {
+    pub speed: f32,
+    pub direction: f32,
+    pub scale_by: Option<Situation>,
+}
+
+impl Rotate {
+    pub fn angular_velocity(&self, difficulty: &Difficulty) -> f32 {
+        let scale = self.scale_by.map(|situation| difficulty.of(situation)).unwrap_or(1.);
+        self.speed * self.direction * scale
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Patrol {
+    pub points: Vec<Vec2>,
+    pub speed: f32,
+    pub phase: f32,
+}
+
+impl Patrol {
+    pub fn position(&self, time: f32) -> Vec2 {
+        let legs: Vec<(Vec2, Vec2)> = self.points.iter().copied()
+            .zip(self.points.iter().copied().cycle().skip(1))
+            .collect();
+        let perimeter: f32 = legs.iter().map(|(a, b)| a.distance(*b)).sum();
+
+        if perimeter <= 0. {
+            return self.points.first().copied().unwrap_or_default();
+        }
+
+        let mut travelled = ((time + self.phase) * self.speed).rem_euclid(perimeter);
+
+        for (a, b) in legs {
+            let length = a.distance(b);
+
+            if travelled <= length {
+                return a.lerp(b, travelled / length);
+            }
+
+            travelled -= length;
+        }
+
+        self.points[0]
+    }
+}
+
+// active while the fraction of the period is between `from` and `to`, wrapping if from > to
+#[derive(Component, Debug, Clone)]
+pub struct PeriodicActiveWindow {
+    pub period: f32,
+    pub from: f32,
+    pub to: f32,
+    pub phase: f32,
+}
+
+impl PeriodicActiveWindow {
+    pub fn is_active(&self, time: f32) -> bool {
+        let fraction = ((time + self.phase) / self.period).rem_euclid(1.);
+
+        match self.from <= self.to {
+            true => fraction >= self.from && fraction < self.to,
+            false => fraction >= self.from || fraction < self.to,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Grow {
+    pub from: f32,
+    pub to: f32,
+    pub seconds: f32,
+    pub phase: f32,
+    pub ping_pong: bool,
+}
+
+impl Grow {
+    pub fn scale(&self, time: f32) -> f32 {
+        let progress = (time + self.phase) / self.seconds;
+
+        let t = match self.ping_pong {
+            true => 1. - (progress.rem_euclid(2.) - 1.).abs(),
+            false => progress.clamp(0., 1.),
+        };
+
+        self.from + (self.to - self.from) * t
+    }
+}
+
+// hazards outside their active window keep their sprite but stop colliding
+#[derive(Component)]
+pub struct Dormant;
+
+#[derive(Component)]
+pub struct ActivateSound(pub Handle<AudioSource>);
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HazardSet;
+
+pub struct HazardsPlugin;
+
+impl Plugin for HazardsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+            oscillate,
+            rotate,
+            patrol,
+            grow,
+            periodic_active_window,
+        )
+            .in_set(HazardSet)
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        );
+    }
+}
+
+fn oscillate(mut query: Query<(&mut Transform, &Oscillate)>, clock: Res<RunClock>) {
+    let time = clock.0.elapsed_secs();
+
+    for (mut transform, oscillate) in &mut query {
+        let value = oscillate.value(time);
+
+        match oscillate.channel {
+            Channel::Rotation => transform.rotation = Quat::from_rotation_z(value),
+            Channel::X => transform.translation.x = value,
+            Channel::Y => transform.translation.y = value,
+        }
+    }
+}
+
+fn rotate(mut query: Query<(&mut Transform, &Rotate)>, time: Res<Time>, difficulty: Res<Difficulty>) {
+    for (mut transform, rotate) in &mut query {
+        transform.rotate_z(rotate.angular_velocity(&difficulty) * time.delta_seconds());
+    }
+}
+
+fn patrol(mut query: Query<(&mut Transform, &Patrol)>, clock: Res<RunClock>) {
+    let time = clock.0.elapsed_secs();
+
+    for (mut transform, patrol) in &mut query {
+        let position = patrol.position(time);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+fn grow(mut query: Query<(&mut Transform, &Grow)>, clock: Res<RunClock>) {
+    let time = clock.0.elapsed_secs();
+
+    for (mut transform, grow) in &mut query {
+        transform.scale = Vec3::splat(grow.scale(time));
+    }
+}
+
+fn periodic_active_window(
+    mut commands: Commands,
+    query: Query<(Entity, &PeriodicActiveWindow, Has<Dormant>, Option<&ActivateSound>)>,
+    clock: Res<RunClock>,
+) {
+    let time = clock.0.elapsed_secs();
+
+    for (entity, window, dormant, sound) in &query {
+        match (window.is_active(time), dormant) {
+            (true, true) => {
+                commands.entity(entity).remove::<Dormant>();
+
+                if let Some(sound) = sound {
+                    commands.spawn(AudioBundle {
+                        source: sound.0.clone(),
+                        settings: PlaybackSettings::DESPAWN,
+                    });
+                }
+            },
+            (false, false) => {
+                commands.entity(entity).insert(Dormant);
+            },
+            _ => {},
+        }
+    }
+}
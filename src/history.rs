@@ -0,0 +1,415 @@
+use std::path::PathBuf;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use crate::app_state::AppState;
+use crate::game::{DifficultyProfile, EndCause, QueuedSeed, Situation};
+use crate::generic_ui::{self, spawn_button, ButtonActionType, List};
+use crate::players::GameMode;
+use crate::replay::Replay;
+use crate::telemetry::{self, PastRun, TelemetrySettings};
+use crate::theme::UiTheme;
+use crate::util;
+
+const PAGE_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SortBy {
+    // newest first, the rest best first
+    #[default]
+    Date,
+    Score,
+    Duration,
+}
+
+impl SortBy {
+    const ALL: [SortBy; 3] = [SortBy::Date, SortBy::Score, SortBy::Duration];
+
+    fn label(self) -> &'static str {
+        match self {
+            SortBy::Date => "newest",
+            SortBy::Score => "best score",
+            SortBy::Duration => "longest",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Ending {
+    #[default]
+    Any,
+    Killed(Situation),
+    GaveUp,
+    Abandoned,
+}
+
+impl Ending {
+    const ALL: [Ending; 6] = [
+        Ending::Any,
+        Ending::Killed(Situation::Baby),
+        Ending::Killed(Situation::Kitten),
+        Ending::Killed(Situation::Sloth),
+        Ending::GaveUp,
+        Ending::Abandoned,
+    ];
+
+    fn label(self) -> String {
+        match self {
+            Ending::Any => "any ending".into(),
+            Ending::Killed(situation) => format!("{:?}", situation).to_lowercase(),
+            Ending::GaveUp => "gave up".into(),
+            Ending::Abandoned => "abandoned".into(),
+        }
+    }
+
+    fn matches(self, run: &PastRun) -> bool {
+        match self {
+            Ending::Any => true,
+            Ending::Killed(situation) => run.cause == EndCause::Collision && run.situation == Some(situation),
+            Ending::GaveUp => run.cause == EndCause::GaveUp,
+            Ending::Abandoned => run.cause == EndCause::Abandoned,
+        }
+    }
+}
+
+const MODES: [Option<GameMode>; 3] = [None, Some(GameMode::Solo), Some(GameMode::Coop)];
+
+fn cycle<T: Copy + PartialEq>(options: &[T], current: T) -> T {
+    let index = options.iter().position(|option| *option == current).unwrap_or(0);
+    options[(index + 1) % options.len()]
+}
+
+// the screen is rebuilt from this whenever it changes
+#[derive(Resource, Default)]
+struct HistoryView {
+    runs: Vec<PastRun>,
+    sort: SortBy,
+    mode: Option<GameMode>,
+    ending: Ending,
+    page: usize,
+    selected: Option<PathBuf>,
+}
+
+impl HistoryView {
+    fn visible(&self) -> Vec<&PastRun> {
+        let mut runs: Vec<&PastRun> = self.runs.iter()
+            .filter(|run| self.mode.is_none_or(|mode| run.mode == mode))
+            .filter(|run| self.ending.matches(run))
+            .collect();
+
+        match self.sort {
+            SortBy::Date => runs.sort_by_key(|run| std::cmp::Reverse(run.started)),
+            SortBy::Score => runs.sort_by_key(|run| std::cmp::Reverse(run.score)),
+            SortBy::Duration => runs.sort_by(|a, b| b.survived.total_cmp(&a.survived)),
+        }
+
+        runs
+    }
+
+    fn pages(&self, visible: usize) -> usize {
+        visible.div_ceil(PAGE_SIZE).max(1)
+    }
+}
+
+// the profile and mode to go back to once a replay or retry from here is over
+#[derive(Resource)]
+struct FromHistory {
+    profile: DifficultyProfile,
+    mode: GameMode,
+}
+
+#[derive(Component)]
+struct HistoryEntity;
+
+#[derive(Component)]
+struct RunList;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<HistoryView>()
+        .add_systems(OnEnter(AppState::History), load_history)
+        .add_systems(Update, (show_history, pick_run).chain().run_if(in_state(AppState::History)))
+        .add_systems(OnExit(AppState::History), util::cleanup_system::<HistoryEntity>)
+        // on leaving the run itself, so "try again" starts a normal run rather than rewinding the replay under a new seed
+        .add_systems(OnExit(AppState::Game), back_from_history);
+    }
+}
+
+fn load_history(mut view: ResMut<HistoryView>, settings: Res<TelemetrySettings>) {
+    // sorting and filters are kept between visits, the runs are read fresh
+    view.runs = telemetry::read_history(&settings.directory);
+    view.page = 0;
+
+    if view.selected.as_ref().is_some_and(|path| !view.runs.iter().any(|run| run.path == *path)) {
+        view.selected = None;
+    }
+}
+
+fn back_from_history(mut commands: Commands, from_history: Option<Res<FromHistory>>) {
+    if let Some(from_history) = from_history {
+        commands.insert_resource(from_history.profile.clone());
+        commands.insert_resource(from_history.mode);
+        commands.remove_resource::<FromHistory>();
+        commands.remove_resource::<Replay>();
+    }
+}
+
+// utc, worked out by hand rather than pulling in a date crate for one line of text
+fn date(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let minutes = (seconds % 86_400) / 60;
+
+    // days since 1970 to a calendar date, after Howard Hinnant's civil_from_days
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{}-{:02}-{:02} {:02}:{:02} utc", year, month, day, minutes / 60, minutes % 60)
+}
+
+fn duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn mode_name(mode: GameMode) -> &'static str {
+    match mode {
+        GameMode::Solo => "solo",
+        GameMode::Coop => "co-op",
+    }
+}
+
+fn ending(run: &PastRun) -> String {
+    match (run.cause, run.situation) {
+        (EndCause::GaveUp, _) => "gave up".into(),
+        (EndCause::TimeLimit, _) => "time limit".into(),
+        (EndCause::Abandoned, _) => "abandoned".into(),
+        (EndCause::Collision, Some(situation)) => format!("{:?}", situation).to_lowercase(),
+        (EndCause::Collision, None) => "collision".into(),
+    }
+}
+
+fn row<'w, 's, 'a>(parent: &'a mut ChildBuilder<'w, 's, '_>, theme: &UiTheme) -> EntityCommands<'w, 's, 'a> {
+    parent.spawn(NodeBundle {
+        style: Style {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Start,
+            column_gap: Val::Px(theme.spacing * 4.),
+            ..default()
+        },
+        ..default()
+    })
+}
+
+fn change_view(change: impl Fn(&mut HistoryView) + Send + Sync + 'static) -> ButtonActionType {
+    ButtonActionType::callback(move |world| {
+        let mut view = world.resource_mut::<HistoryView>();
+        change(&mut view);
+    })
+}
+
+// replay presses the recorded rescues, retry only starts from the same seed
+fn relaunch(run: PastRun, replay: bool) -> ButtonActionType {
+    ButtonActionType::callback(move |world| {
+        if replay {
+            match telemetry::read_run(&run.path) {
+                Ok(recorded) => world.insert_resource(Replay::new(&recorded)),
+                Err(error) => {
+                    warn!("could not replay: {}", error);
+                    return;
+                },
+            }
+        }
+
+        if !world.contains_resource::<FromHistory>() {
+            let profile = world.resource::<DifficultyProfile>().clone();
+            let mode = *world.resource::<GameMode>();
+            world.insert_resource(FromHistory { profile, mode });
+        }
+
+        if let Some(profile) = DifficultyProfile::preset(&run.profile) {
+            world.insert_resource(profile);
+        }
+
+        world.insert_resource(run.mode);
+        world.resource_mut::<QueuedSeed>().0 = run.seed;
+        world.resource_mut::<NextState<AppState>>().set(AppState::Game);
+    })
+}
+
+fn show_history(
+    mut commands: Commands,
+    view: Res<HistoryView>,
+    existing: Query<Entity, With<HistoryEntity>>,
+    theme: Res<UiTheme>,
+    settings: Res<TelemetrySettings>,
+) {
+    if !view.is_changed() {
+        return;
+    }
+
+    for entity in &existing {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let visible = view.visible();
+    let pages = view.pages(visible.len());
+    let page = view.page.min(pages - 1);
+    let shown: Vec<&PastRun> = visible.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE).copied().collect();
+    let selected = view.selected.as_ref().and_then(|path| view.runs.iter().find(|run| run.path == *path));
+
+    commands
+        .spawn((generic_ui::screen(), HistoryEntity))
+        .with_children(|parent| {
+            generic_ui::title(parent, &theme, "history");
+
+            row(parent, &theme).with_children(|controls| {
+                spawn_button(controls, &theme, view.sort.label(), change_view(|view| {
+                    view.sort = cycle(&SortBy::ALL, view.sort);
+                    view.page = 0;
+                }));
+
+                spawn_button(controls, &theme, view.mode.map_or("all modes", mode_name), change_view(|view| {
+                    view.mode = cycle(&MODES, view.mode);
+                    view.page = 0;
+                }));
+
+                spawn_button(controls, &theme, &view.ending.label(), change_view(|view| {
+                    view.ending = cycle(&Ending::ALL, view.ending);
+                    view.page = 0;
+                }));
+            });
+
+            row(parent, &theme).with_children(|columns| {
+                columns
+                    .spawn(NodeBundle {
+                        style: Style {
+                            display: Display::Flex,
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|column| {
+                        if shown.is_empty() {
+                            let message = match view.runs.is_empty() {
+                                true => format!("no finished runs in {}", settings.directory.display()),
+                                false => "no runs match".into(),
+                            };
+
+                            generic_ui::label(column, &theme, message);
+                            return;
+                        }
+
+                        let rows: Vec<String> = shown.iter()
+                            .map(|run| format!("{}  {}  {}  {}  {}", date(run.started), mode_name(run.mode), run.score, duration(run.survived), ending(run)))
+                            .collect();
+
+                        let index = shown.iter().position(|run| Some(&run.path) == view.selected.as_ref());
+                        generic_ui::list(column, &theme, &rows).insert((List { selected: index }, RunList));
+
+                        row(column, &theme).with_children(|paging| {
+                            if page > 0 {
+                                spawn_button(paging, &theme, "newer", change_view(move |view| view.page = page - 1));
+                            }
+
+                            generic_ui::text(paging, &theme, format!("{}/{}", page + 1, pages), theme.small_text_size, theme.text);
+
+                            if page + 1 < pages {
+                                spawn_button(paging, &theme, "older", change_view(move |view| view.page = page + 1));
+                            }
+                        });
+                    });
+
+                if let Some(run) = selected {
+                    spawn_details(columns, &theme, run);
+                }
+            });
+
+            spawn_button(parent, &theme, "back", ButtonActionType::ChangeAppState(AppState::MainMenu));
+        });
+}
+
+fn spawn_details(parent: &mut ChildBuilder, theme: &UiTheme, run: &PastRun) {
+    let unlocks = match run.unlocks.is_empty() {
+        true => "nothing unlocked".to_string(),
+        false => {
+            let names: Vec<String> = run.unlocks.iter().map(|situation| format!("{:?}", situation).to_lowercase()).collect();
+            format!("unlocked {}", names.join(", "))
+        },
+    };
+
+    let lines = [
+        date(run.started),
+        format!("{} on {}", mode_name(run.mode), run.profile),
+        format!("score {} ({} bonus)", run.score, run.bonus),
+        format!("lasted {}", duration(run.survived)),
+        format!("{} rescues, {} near misses", run.rescues, run.near_misses),
+        unlocks,
+        match run.seed {
+            Some(seed) => format!("seed {}", seed),
+            None => "recorded without a seed".into(),
+        },
+    ];
+
+    generic_ui::panel(parent, theme).with_children(|panel| {
+        for line in lines {
+            generic_ui::text(panel, theme, line, theme.small_text_size, theme.text);
+        }
+
+        generic_ui::text(panel, theme, &run.message, theme.small_text_size * 0.75, theme.danger)
+            .insert(Style { max_width: Val::Px(theme.button_size.x * 2.), ..default() });
+
+        // both start the same world, without a seed neither would
+        if run.seed.is_some() {
+            spawn_button(panel, theme, "replay", relaunch(run.clone(), true));
+            spawn_button(panel, theme, "retry seed", relaunch(run.clone(), false));
+        }
+    });
+}
+
+fn pick_run(lists: Query<&List, (With<RunList>, Changed<List>)>, mut view: ResMut<HistoryView>) {
+    let Ok(list) = lists.get_single() else {
+        return;
+    };
+
+    let visible = view.visible();
+    let page = view.page.min(view.pages(visible.len()) - 1);
+
+    let picked = list.selected
+        .and_then(|index| visible.get(page * PAGE_SIZE + index))
+        .map(|run| run.path.clone());
+
+    // only a real change rebuilds the screen, the rebuilt list reports its selection too
+    if picked.is_some() && picked != view.selected {
+        view.selected = picked;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(date(0), "1970-01-01 00:00 utc");
+        assert_eq!(date(1_709_210_040_000), "2024-02-29 12:34 utc");
+        assert_eq!(date(946_684_740_000), "1999-12-31 23:59 utc");
+        assert_eq!(date(946_684_800_000), "2000-01-01 00:00 utc");
+        // 2100 isn't a leap year
+        assert_eq!(date(4_107_456_000_000), "2100-02-28 00:00 utc");
+        assert_eq!(date(4_107_542_400_000), "2100-03-01 00:00 utc");
+        // milliseconds are dropped, not rounded
+        assert_eq!(date(59_999), "1970-01-01 00:00 utc");
+    }
+}
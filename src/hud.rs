@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use crate::app_state::AppState;
+use crate::broadphase::HazardGrid;
+use crate::chaos::Swarm;
+use crate::game::{CircleCollider, Difficulty, GameEntity, GameSetup, GoodThing, RunClock, Score};
+use crate::generic_ui::{self, Meter};
+use crate::milestones::{Milestones, Reached};
+use crate::theme::UiTheme;
+
+// a good thing this far from the nearest bad thing shows an empty danger meter
+const DANGER_RANGE: f32 = 300.;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum HudText {
+    Score,
+    Clock,
+    Difficulty,
+    NextUnlock,
+}
+
+#[derive(Component)]
+struct DangerList;
+
+#[derive(Component)]
+struct DangerRow(Entity);
+
+#[derive(Component)]
+struct DangerMeter(Entity);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnEnter(AppState::Game), spawn_hud.after(GameSetup))
+        .add_systems(Update, (
+            add_danger_meters,
+            remove_danger_meters,
+            update_text,
+            update_danger,
+        ).run_if(in_state(AppState::Game)));
+    }
+}
+
+fn spawn_hud(mut commands: Commands, theme: Res<UiTheme>) {
+    commands
+        .spawn((generic_ui::anchored(FlexDirection::Column, AlignItems::End, JustifyContent::Start), GameEntity))
+        .with_children(|parent| {
+            for text in [HudText::Score, HudText::Clock, HudText::Difficulty, HudText::NextUnlock] {
+                generic_ui::text(parent, &theme, "", theme.small_text_size, theme.text).insert(text);
+            }
+
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        display: Display::Flex,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::End,
+                        ..default()
+                    },
+                    ..default()
+                },
+                DangerList,
+            ));
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn add_danger_meters(
+    mut commands: Commands,
+    theme: Res<UiTheme>,
+    good_things: Query<(Entity, &GoodThing), (Added<GoodThing>, Without<Swarm>)>,
+    lists: Query<Entity, With<DangerList>>,
+) {
+    let Ok(list) = lists.get_single() else {
+        return;
+    };
+
+    for (entity, good_thing) in &good_things {
+        commands.entity(list).with_children(|list| {
+            list.spawn((
+                NodeBundle {
+                    style: Style {
+                        display: Display::Flex,
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                },
+                DangerRow(entity),
+            ))
+            .with_children(|row| {
+                generic_ui::text(row, &theme, format!("{:?}", good_thing.situation).to_lowercase(), theme.small_text_size, theme.text);
+                generic_ui::meter(row, &theme, 0., theme.danger).insert(DangerMeter(entity));
+            });
+        });
+    }
+}
+
+fn remove_danger_meters(mut commands: Commands, mut removed: RemovedComponents<GoodThing>, rows: Query<(Entity, &DangerRow)>) {
+    for good_thing in removed.read() {
+        for (row, danger) in &rows {
+            if danger.0 == good_thing {
+                commands.entity(row).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn update_text(
+    mut texts: Query<(&mut Text, &HudText)>,
+    score: Res<Score>,
+    clock: Res<RunClock>,
+    difficulty: Res<Difficulty>,
+    milestones: Res<Milestones>,
+    reached: Res<Reached>,
+) {
+    for (mut text, hud_text) in &mut texts {
+        let value = match hud_text {
+            HudText::Score => format!("score: {}", score.0),
+            HudText::Clock => {
+                let seconds = clock.0.elapsed_secs() as u32;
+                format!("time: {}:{:02}", seconds / 60, seconds % 60)
+            },
+            HudText::Difficulty => format!("baby {:.1}  sloth {:.1}  kitten {:.1}", difficulty.baby, difficulty.sloth, difficulty.kitten),
+            HudText::NextUnlock => match milestones.next(&reached, &score, &clock, &difficulty) {
+                Some(milestone) => format!(
+                    "next unlock: {} at {}",
+                    milestone.name.replace('_', " "),
+                    milestone.condition.describe(&score, &clock, &difficulty),
+                ),
+                None => "everything unlocked".into(),
+            },
+        };
+
+        // only touch the text when it reads differently, so the layout isn't redone every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_danger(
+    mut meters: Query<(&mut Meter, &DangerMeter)>,
+    good_things: Query<(&CircleCollider, &GlobalTransform), With<GoodThing>>,
+    hazards: Res<HazardGrid>,
+) {
+    for (mut meter, danger) in &mut meters {
+        let Ok((good_circle, good_transform)) = good_things.get(danger.0) else {
+            continue;
+        };
+
+        let position = good_transform.translation().xy();
+        let radius = good_circle.radius * good_transform.compute_transform().scale.x;
+
+        // anything further than the meter's range reads as zero anyway
+        let nearest = hazards.0.overlapping(position, radius + DANGER_RANGE)
+            .filter(|(hazard, _, _)| !hazard.dormant)
+            .map(|(_, bad_position, bad_radius)| position.distance(bad_position) - radius - bad_radius)
+            .fold(f32::INFINITY, f32::min);
+
+        let value = (1. - nearest / DANGER_RANGE).clamp(0., 1.);
+
+        if (meter.value - value).abs() > 0.01 {
+            meter.value = value;
+        }
+    }
+}
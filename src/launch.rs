@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use bevy::{prelude::*, utils::tracing, window::PresentMode};
+use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter};
+use crate::app_state::AppState;
+use crate::camera::Scaling;
+use crate::game::DifficultyProfile;
+use crate::players::GameMode;
+use crate::startup;
+use crate::theme::UiTheme;
+
+pub const USAGE: &str = "usage: do_something [options]
+       do_something soak [runs] [reaction time] [error rate] [options]
+       do_something summarize [directory]
+       do_something bench [counts...]
+
+defaults come from config.toml in the user config directory, these override them
+
+options:
+  --seed N                 seed every run's random numbers with N, soak runs with N, N+1...
+  --mode solo|coop
+  --start-state menu|game|gameover|mods|history
+  --difficulty-preset NAME gentle, standard, brisk or frantic, soak only runs this one
+  --chaos N                add N more of every situation, scattered over a bigger field
+  --headless               run without a window or sound
+  --ticks N                quit after N frames
+  --replay FILE            play back the rescues from a telemetry log
+  --windowed, --fullscreen
+  --size WxH
+  --scaling fit|fill|pixel-perfect
+  --reduced-motion         no screen shake or zooming
+  --mute
+  --log FILE               copy the log to FILE
+  --no-telemetry
+  --help";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowChoice {
+    #[default]
+    Windowed,
+    Fullscreen,
+}
+
+// how the game starts: the config file's settings with the command line layered on top
+#[derive(Resource, Debug, Clone)]
+pub struct LaunchConfig {
+    pub title: String,
+    pub present_mode: PresentMode,
+    pub clear_colour: Color,
+    pub music: Option<String>,
+    pub volume: f32,
+    pub seed: Option<u64>,
+    pub mode: Option<GameMode>,
+    pub start_state: Option<AppState>,
+    pub difficulty_preset: Option<DifficultyProfile>,
+    pub chaos: Option<u32>,
+    pub headless: bool,
+    pub ticks: Option<u32>,
+    pub replay: Option<PathBuf>,
+    pub window: WindowChoice,
+    pub size: Vec2,
+    pub scaling: Scaling,
+    pub reduced_motion: bool,
+    pub mute: bool,
+    pub log: Option<PathBuf>,
+    pub telemetry: bool,
+}
+
+impl Default for LaunchConfig {
+    fn default() -> Self {
+        LaunchConfig {
+            title: startup::TITLE.into(),
+            present_mode: PresentMode::AutoVsync,
+            clear_colour: UiTheme::CLEAR_COLOUR,
+            music: Some(startup::MUSIC.into()),
+            volume: 1.,
+            seed: None,
+            mode: None,
+            start_state: None,
+            difficulty_preset: None,
+            chaos: None,
+            headless: false,
+            ticks: None,
+            replay: None,
+            window: WindowChoice::Windowed,
+            size: startup::RESOLUTION.into(),
+            scaling: Scaling::Fit,
+            reduced_motion: false,
+            mute: false,
+            log: None,
+            telemetry: true,
+        }
+    }
+}
+
+impl LaunchConfig {
+    pub fn with_args(self, args: &[String]) -> Result<Self, String> {
+        let mut config = self;
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+
+            match flag.as_str() {
+                "--seed" => config.seed = Some(parse_number(flag, value()?)?),
+                "--mode" => config.mode = Some(parse_mode(value()?)?),
+                "--start-state" => config.start_state = Some(AppState::parse(value()?)?),
+                "--difficulty-preset" => {
+                    let name = value()?;
+                    let preset = DifficultyProfile::preset(name)
+                        .ok_or_else(|| format!("unknown difficulty preset {}", name))?;
+                    config.difficulty_preset = Some(preset);
+                },
+                "--chaos" => config.chaos = Some(parse_number(flag, value()?)?),
+                "--headless" => config.headless = true,
+                "--ticks" => config.ticks = Some(parse_number(flag, value()?)?),
+                "--replay" => config.replay = Some(PathBuf::from(value()?)),
+                "--windowed" => config.window = WindowChoice::Windowed,
+                "--fullscreen" => config.window = WindowChoice::Fullscreen,
+                "--size" => config.size = parse_size(value()?)?,
+                "--scaling" => config.scaling = parse_scaling(value()?)?,
+                "--reduced-motion" => config.reduced_motion = true,
+                "--mute" => config.mute = true,
+                "--log" => config.log = Some(PathBuf::from(value()?)),
+                "--no-telemetry" => config.telemetry = false,
+                _ => return Err(format!("unknown option {}", flag)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got {}", flag, value))
+}
+
+pub fn parse_mode(value: &str) -> Result<GameMode, String> {
+    match value {
+        "solo" => Ok(GameMode::Solo),
+        "coop" | "co-op" => Ok(GameMode::Coop),
+        _ => Err(format!("unknown mode {}", value)),
+    }
+}
+
+pub fn parse_scaling(value: &str) -> Result<Scaling, String> {
+    match value {
+        "fit" => Ok(Scaling::Fit),
+        "fill" => Ok(Scaling::Fill),
+        "pixel-perfect" | "pixel_perfect" => Ok(Scaling::PixelPerfect),
+        _ => Err(format!("unknown scaling {}", value)),
+    }
+}
+
+fn parse_size(value: &str) -> Result<Vec2, String> {
+    let invalid = || format!("size should look like 1024x720, got {}", value);
+
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width: f32 = width.parse().map_err(|_| invalid())?;
+    let height: f32 = height.parse().map_err(|_| invalid())?;
+
+    match width >= 1. && height >= 1. {
+        true => Ok(Vec2::new(width, height)),
+        false => Err(invalid()),
+    }
+}
+
+// bevy's LogPlugin can't write to a file, so this replaces it when --log is given
+pub fn log_to_file(path: &Path) -> bool {
+    let file = match File::create(path) {
+        Ok(file) => file,
+        Err(error) => {
+            eprintln!("could not open log file {}: {}", path.display(), error);
+            return false;
+        },
+    };
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info,wgpu=error,naga=warn"));
+
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(fmt::layer().with_ansi(false).with_writer(Mutex::new(file)));
+
+    tracing::subscriber::set_global_default(subscriber).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn error(line: &str) -> String {
+        LaunchConfig::default().with_args(&args(line)).unwrap_err()
+    }
+
+    #[test]
+    fn flags_set_what_they_name() {
+        let config = LaunchConfig::default()
+            .with_args(&args("--seed 42 --mode co-op --start-state history --difficulty-preset brisk --size 800x600 --scaling pixel-perfect --fullscreen --mute --no-telemetry"))
+            .unwrap();
+
+        assert_eq!(config.seed, Some(42));
+        assert_eq!(config.mode, Some(GameMode::Coop));
+        assert_eq!(config.start_state, Some(AppState::History));
+        assert_eq!(config.difficulty_preset.map(|preset| preset.name), Some("brisk".to_string()));
+        assert_eq!(config.size, Vec2::new(800., 600.));
+        assert_eq!(config.scaling, Scaling::PixelPerfect);
+        assert_eq!(config.window, WindowChoice::Fullscreen);
+        assert!(config.mute);
+        assert!(!config.telemetry);
+    }
+
+    #[test]
+    fn later_flags_win() {
+        let config = LaunchConfig::default().with_args(&args("--fullscreen --windowed --seed 1 --seed 2")).unwrap();
+
+        assert_eq!(config.window, WindowChoice::Windowed);
+        assert_eq!(config.seed, Some(2));
+    }
+
+    #[test]
+    fn errors_name_the_flag_and_value() {
+        assert_eq!(error("--seed"), "--seed needs a value");
+        assert_eq!(error("--mute --log"), "--log needs a value");
+        assert_eq!(error("--seed lots"), "--seed expects a number, got lots");
+        assert_eq!(error("--chaos -3"), "--chaos expects a number, got -3");
+        assert_eq!(error("--mode versus"), "unknown mode versus");
+        assert_eq!(error("--scaling stretch"), "unknown scaling stretch");
+        assert_eq!(error("--start-state credits"), "unknown state credits");
+        assert_eq!(error("--difficulty-preset nightmare"), "unknown difficulty preset nightmare");
+        assert_eq!(error("--fast"), "unknown option --fast");
+        assert_eq!(error("42"), "unknown option 42");
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1024x720"), Ok(Vec2::new(1024., 720.)));
+        assert_eq!(parse_size("1x1"), Ok(Vec2::ONE));
+
+        for bad in ["1024", "1024x", "x720", "1024X720", "1024x720x2", "0x720", "1024x0", "-800x600", "wide x tall"] {
+            assert_eq!(parse_size(bad), Err(format!("size should look like 1024x720, got {}", bad)));
+        }
+    }
+
+    #[test]
+    fn start_states() {
+        // the same names the console's state command takes
+        for (name, state) in AppState::NAMES {
+            assert_eq!(AppState::parse(name), Ok(state));
+        }
+
+        assert_eq!(AppState::parse("gameover"), Ok(AppState::GameOver));
+        assert_eq!(AppState::parse("Game"), Err("unknown state Game".to_string()));
+        assert_eq!(AppState::parse(""), Err("unknown state ".to_string()));
+    }
+}
@@ -9,25 +9,168 @@ mod startup;
 pub mod game;
 mod game_over;
 pub mod generic_ui;
+pub mod theme;
 pub mod util;
+pub mod players;
+pub mod ai;
+mod soak;
+mod telemetry;
+mod pause;
+mod save;
+pub mod tween;
+pub mod hazards;
+pub mod motion;
+mod stamina;
+mod hud;
+mod telegraph;
+mod combo;
+mod mods;
+mod scripting;
+mod launch;
+mod config;
+mod camera;
+#[cfg(feature = "debug")]
+mod debug_overlay;
+#[cfg(feature = "debug")]
+mod console;
+mod replay;
+mod broadphase;
+mod chaos;
+mod milestones;
+mod history;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).is_some_and(|arg| arg == "summarize") {
+        let directory = args.get(2).map(String::as_str).unwrap_or("logs");
+        telemetry::summarize(std::path::Path::new(directory));
+        return;
+    }
+
+    if args.get(1).is_some_and(|arg| arg == "bench") {
+        broadphase::bench(&args[2..]);
+        return;
+    }
+
+    let soak = args.get(1).is_some_and(|arg| arg == "soak");
+
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", launch::USAGE);
+        return;
+    }
+
+    // soak takes its own positional arguments, before any flags
+    let soak_args = match soak {
+        true => &args[2..2 + args[2..].iter().take_while(|arg| !arg.starts_with("--")).count()],
+        false => &args[..0],
+    };
+
+    let flags = match soak {
+        true => &args[2 + soak_args.len()..],
+        false => &args[1..],
+    };
+
+    // the config file sets the defaults and flags override them
+    let launch = config::load().with_args(flags).map(|launch| match soak {
+        true => launch::LaunchConfig { headless: true, ..launch },
+        false => launch,
+    });
+
+    let mut launch = launch.unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, launch::USAGE);
+        std::process::exit(2);
+    });
+
     let mut app = App::new();
 
-    app.add_plugins(startup::StartupPlugin);
+    if let Some(path) = launch.replay.clone() {
+        let run = telemetry::read_run(&path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        });
+
+        launch.seed = run.seed.or(launch.seed);
+        launch.mode = Some(run.mode);
+        launch.difficulty_preset = game::DifficultyProfile::preset(&run.profile).or(launch.difficulty_preset);
+        launch.start_state = Some(app_state::AppState::Game);
+
+        app.insert_resource(replay::Replay::new(&run));
+    }
+
+    app.insert_resource(launch.clone());
+
+    mods::register_source(&mut app);
+
+    if launch.headless {
+        app.add_plugins(startup::HeadlessPlugin);
+    }
+    else {
+        app.add_plugins(startup::StartupPlugin);
+    }
 
     #[cfg(feature = "debug")]
-    app.add_plugins(WorldInspectorPlugin::new());
+    if !launch.headless {
+        app.add_plugins((WorldInspectorPlugin::new(), debug_overlay::DebugOverlayPlugin, console::ConsolePlugin));
+    }
 
     app.add_plugins(app_state::AppStatePlugin);
+
+    app.add_plugins(camera::CameraPlugin);
+
+    app.add_plugins(tween::TweenPlugin);
+
+    app.add_plugins(generic_ui::GenericUiPlugin);
+
+    app.add_plugins(players::PlayersPlugin);
     
     app.add_plugins(main_menu::MainMenuPlugin);
 
     app.add_plugins(game::GamePlugin);
 
+    app.add_plugins(milestones::MilestonesPlugin);
+
+    app.add_plugins(broadphase::BroadphasePlugin);
+
+    app.add_plugins(chaos::ChaosPlugin);
+
+    app.add_plugins(hazards::HazardsPlugin);
+
+    app.add_plugins(motion::MotionPlugin);
+
+    app.add_plugins(stamina::StaminaPlugin);
+
+    app.add_plugins(hud::HudPlugin);
+
+    app.add_plugins(telegraph::TelegraphPlugin);
+
+    app.add_plugins(combo::ComboPlugin);
+
+    app.add_plugins(mods::ModsPlugin);
+
+    app.add_plugins(scripting::ScriptingPlugin);
+
+    app.add_plugins(pause::PausePlugin);
+
+    app.add_plugins(save::SavePlugin);
+
     app.add_plugins(game_over::GameOverPlugin);
 
-    app.add_systems(Update, generic_ui::button_interaction_system);
+    app.add_plugins(ai::AiPlugin);
+
+    app.add_plugins(telemetry::TelemetryPlugin);
+
+    app.add_plugins(replay::ReplayPlugin);
+
+    app.add_plugins(history::HistoryPlugin);
+
+    if !launch.telemetry {
+        app.insert_resource(telemetry::TelemetrySettings { enabled: false, ..default() });
+    }
+
+    if soak {
+        app.add_plugins(soak::SoakPlugin::from_args(soak_args));
+    }
 
     app.run();
 }
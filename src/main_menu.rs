@@ -1,7 +1,10 @@
 use bevy::prelude::*;
 use crate::app_state::AppState;
 use crate::util as util;
-use crate::generic_ui::{ ButtonActionType, spawn_button };
+use crate::generic_ui::{ self, ButtonActionType, spawn_button };
+use crate::players::GameMode;
+use crate::save::{SaveActions, SaveSettings};
+use crate::theme::UiTheme;
 
 pub struct MainMenuPlugin;
 impl Plugin for MainMenuPlugin {
@@ -17,26 +20,29 @@ impl Plugin for MainMenuPlugin {
 struct MainMenuEntity;
 
 
-fn setup_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
-
-    let node = NodeBundle {
-        style: Style {
-            width: Val::Percent(100.0),
-            height: Val::Percent(100.0),
-            display: Display::Flex,
-            flex_direction: FlexDirection::Column,
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
-            ..default()
-        },
-        ..default()
-    };
-
+fn setup_menu(mut commands: Commands, asset_server: Res<AssetServer>, save_settings: Res<SaveSettings>, save_actions: Res<SaveActions>, theme: Res<UiTheme>) {
     commands
-        .spawn((node, MainMenuEntity))
+        .spawn((generic_ui::screen(), MainMenuEntity))
         .with_children(|parent| {
-            spawn_button(parent, &asset_server, "Play", ButtonActionType::ChangeAppState(AppState::Game));
+            if save_settings.exists() {
+                spawn_button(parent, &theme, "Continue", ButtonActionType::Run(save_actions.continue_run));
+            }
+
+            spawn_button(parent, &theme, "Play", start_game(GameMode::Solo));
+
+            spawn_button(parent, &theme, "Co-op", start_game(GameMode::Coop));
+
+            spawn_button(parent, &theme, "Mods", ButtonActionType::ChangeAppState(AppState::Mods));
+
+            spawn_button(parent, &theme, "History", ButtonActionType::ChangeAppState(AppState::History));
         });
 
     commands.spawn((util::image(Vec2::new(0., 220.), "logo.png".into(), &asset_server), MainMenuEntity));
-}
\ No newline at end of file
+}
+
+fn start_game(mode: GameMode) -> ButtonActionType {
+    ButtonActionType::callback(move |world| {
+        world.insert_resource(mode);
+        world.resource_mut::<NextState<AppState>>().set(AppState::Game);
+    })
+}
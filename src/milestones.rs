@@ -0,0 +1,273 @@
+use bevy::{ecs::system::SystemId, prelude::*, utils::HashSet};
+use crate::app_state::AppState;
+use crate::game::{Difficulty, GameSetup, RunClock, Score, Situation};
+use crate::pause;
+
+// difficulty climbs in float steps of 0.1, so landing a hair under the target still counts
+const SLACK: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Condition {
+    Score(i32),
+    Seconds(f32),
+    Difficulty(Situation, f32),
+}
+
+impl Condition {
+    // where the run is and where it needs to be
+    fn values(&self, score: &Score, clock: &RunClock, difficulty: &Difficulty) -> (f32, f32) {
+        match *self {
+            Condition::Score(target) => (score.0 as f32, target as f32),
+            Condition::Seconds(target) => (clock.0.elapsed_secs(), target),
+            Condition::Difficulty(situation, target) => (difficulty.of(situation), target),
+        }
+    }
+
+    pub fn met(&self, score: &Score, clock: &RunClock, difficulty: &Difficulty) -> bool {
+        let (value, target) = self.values(score, clock, difficulty);
+        value + SLACK >= target
+    }
+
+    // zero at the start of a run, one once met
+    pub fn progress(&self, score: &Score, clock: &RunClock, difficulty: &Difficulty) -> f32 {
+        let (value, target) = self.values(score, clock, difficulty);
+
+        match target > 0. {
+            true => (value / target).clamp(0., 1.),
+            false => 1.,
+        }
+    }
+
+    pub fn describe(&self, score: &Score, clock: &RunClock, difficulty: &Difficulty) -> String {
+        let (value, target) = self.values(score, clock, difficulty);
+
+        match self {
+            Condition::Score(_) => format!("score {}/{}", value, target),
+            Condition::Seconds(_) => format!("{:.0}s/{:.0}s", value, target),
+            Condition::Difficulty(situation, _) => format!("{:?} {:.1}/{:.1}", situation, value, target).to_lowercase(),
+        }
+    }
+}
+
+pub struct Milestone {
+    pub name: String,
+    pub condition: Condition,
+    handler: SystemId,
+}
+
+// in the order they were declared, which is the order ties are shown on the hud
+#[derive(Resource, Default)]
+pub struct Milestones(pub Vec<Milestone>);
+
+// names of the milestones this run has already passed
+#[derive(Resource, Default)]
+pub struct Reached(pub HashSet<String>);
+
+#[derive(Event)]
+pub struct MilestoneReached(pub String);
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MilestoneSet;
+
+pub trait MilestoneExt {
+    fn milestone<M>(&mut self, name: &'static str, condition: Condition, handler: impl IntoSystem<(), (), M> + 'static) -> &mut Self;
+}
+
+impl MilestoneExt for App {
+    fn milestone<M>(&mut self, name: &'static str, condition: Condition, handler: impl IntoSystem<(), (), M> + 'static) -> &mut Self {
+        let handler = self.world.register_system(handler);
+
+        self.world.get_resource_or_insert_with(Milestones::default).add(name.to_string(), condition, handler);
+        self
+    }
+}
+
+impl Milestones {
+    // for milestones only known once the game is running, like the ones mod packs bring
+    pub fn add(&mut self, name: String, condition: Condition, handler: SystemId) {
+        self.0.push(Milestone { name, condition, handler });
+    }
+
+    // hands back the handler, for whoever registered it to remove
+    pub fn remove(&mut self, name: &str) -> Option<SystemId> {
+        let index = self.0.iter().position(|milestone| milestone.name == name)?;
+        Some(self.0.remove(index).handler)
+    }
+
+    // the unreached milestone closest to being reached
+    pub fn next(&self, reached: &Reached, score: &Score, clock: &RunClock, difficulty: &Difficulty) -> Option<&Milestone> {
+        self.0.iter()
+            .filter(|milestone| !reached.0.contains(&milestone.name))
+            .map(|milestone| (milestone, milestone.condition.progress(score, clock, difficulty)))
+            .fold(None, |best: Option<(&Milestone, f32)>, (milestone, progress)| match best {
+                Some((_, best_progress)) if best_progress >= progress => best,
+                _ => Some((milestone, progress)),
+            })
+            .map(|(milestone, _)| milestone)
+    }
+}
+
+pub struct MilestonesPlugin;
+
+impl Plugin for MilestonesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Milestones>()
+        .init_resource::<Reached>()
+        .add_event::<MilestoneReached>()
+        .add_systems(OnEnter(AppState::Game), reset_reached.before(GameSetup))
+        // checked once on entering too, so a resumed run rebuilds everything it had unlocked before its first frame
+        .add_systems(OnEnter(AppState::Game), (apply_deferred, check_milestones).chain().in_set(MilestoneSet).after(GameSetup))
+        .add_systems(Update, check_milestones
+            .in_set(MilestoneSet)
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        );
+
+        #[cfg(feature = "debug")]
+        console_commands(app);
+    }
+}
+
+#[cfg(feature = "debug")]
+fn console_commands(app: &mut App) {
+    use crate::console::{self, ConsoleCommands, ConsoleExt};
+
+    app.console_command("milestone", "milestone NAME runs its handler now, even if the run has already passed it", &[], |world, args| {
+        let name = console::arg(args, 0, "milestone")?;
+        reach(world, name)?;
+        Ok(format!("reached {}", name))
+    });
+
+    // milestones are declared by other plugins, so the names are only all known once startup is done
+    app.add_systems(PostStartup, |milestones: Res<Milestones>, mut commands: ResMut<ConsoleCommands>| {
+        commands.complete("milestone", milestones.0.iter().map(|milestone| milestone.name.clone()).collect());
+    });
+}
+
+// runs a milestone's handler straight away and counts it as reached, for cheats
+#[cfg(feature = "debug")]
+pub fn reach(world: &mut World, name: &str) -> Result<(), String> {
+    let milestone = world.resource::<Milestones>().0.iter()
+        .find(|milestone| milestone.name == name)
+        .map(|milestone| (milestone.name.clone(), milestone.handler))
+        .ok_or_else(|| format!("no milestone called {}", name))?;
+
+    world.run_system(milestone.1).map_err(|error| error.to_string())?;
+    world.resource_mut::<Reached>().0.insert(milestone.0.clone());
+    world.send_event(MilestoneReached(milestone.0));
+
+    Ok(())
+}
+
+fn reset_reached(mut reached: ResMut<Reached>) {
+    reached.0.clear();
+}
+
+fn check_milestones(
+    mut commands: Commands,
+    milestones: Res<Milestones>,
+    mut reached: ResMut<Reached>,
+    mut events: EventWriter<MilestoneReached>,
+    score: Res<Score>,
+    clock: Res<RunClock>,
+    difficulty: Res<Difficulty>,
+) {
+    for milestone in &milestones.0 {
+        if reached.0.contains(&milestone.name) || !milestone.condition.met(&score, &clock, &difficulty) {
+            continue;
+        }
+
+        reached.0.insert(milestone.name.clone());
+        commands.run_system(milestone.handler);
+        events.send(MilestoneReached(milestone.name.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn run(score: i32, seconds: f32, sloth: f32) -> (Score, RunClock, Difficulty) {
+        let mut clock = RunClock::default();
+        clock.0.set_elapsed(Duration::from_secs_f32(seconds));
+
+        (Score(score, String::new()), clock, Difficulty { sloth, ..default() })
+    }
+
+    fn declare(conditions: &[(&str, Condition)]) -> Milestones {
+        let mut world = World::new();
+        let mut milestones = Milestones::default();
+
+        for (name, condition) in conditions {
+            milestones.add(name.to_string(), *condition, world.register_system(|| {}));
+        }
+
+        milestones
+    }
+
+    #[test]
+    fn met_within_slack() {
+        let condition = Condition::Difficulty(Situation::Sloth, 1.5);
+
+        let (score, clock, difficulty) = run(0, 0., 1.5 - SLACK / 2.);
+        assert!(condition.met(&score, &clock, &difficulty));
+
+        let (score, clock, difficulty) = run(0, 0., 1.5 - SLACK * 2.);
+        assert!(!condition.met(&score, &clock, &difficulty));
+    }
+
+    #[test]
+    fn met_on_score_and_seconds() {
+        let (score, clock, difficulty) = run(100, 30., 0.);
+
+        assert!(Condition::Score(100).met(&score, &clock, &difficulty));
+        assert!(!Condition::Score(101).met(&score, &clock, &difficulty));
+        assert!(Condition::Seconds(30.).met(&score, &clock, &difficulty));
+        assert!(!Condition::Seconds(31.).met(&score, &clock, &difficulty));
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        let (score, clock, difficulty) = run(50, 0., 0.);
+
+        assert_eq!(Condition::Score(100).progress(&score, &clock, &difficulty), 0.5);
+        assert_eq!(Condition::Score(25).progress(&score, &clock, &difficulty), 1.);
+        assert_eq!(Condition::Score(-10).progress(&score, &clock, &difficulty), 1.);
+        assert_eq!(Condition::Seconds(10.).progress(&score, &clock, &difficulty), 0.);
+    }
+
+    #[test]
+    fn next_is_the_closest_unreached() {
+        let milestones = declare(&[
+            ("far", Condition::Score(1000)),
+            ("close", Condition::Score(100)),
+            ("done", Condition::Score(10)),
+        ]);
+        let (score, clock, difficulty) = run(50, 0., 0.);
+        let mut reached = Reached::default();
+        reached.0.insert("done".to_string());
+
+        let next = milestones.next(&reached, &score, &clock, &difficulty).map(|milestone| milestone.name.as_str());
+        assert_eq!(next, Some("close"));
+
+        reached.0.insert("close".to_string());
+        reached.0.insert("far".to_string());
+        assert!(milestones.next(&reached, &score, &clock, &difficulty).is_none());
+    }
+
+    #[test]
+    fn next_ties_go_to_the_first_declared() {
+        let milestones = declare(&[
+            ("first", Condition::Seconds(20.)),
+            ("second", Condition::Score(100)),
+            ("third", Condition::Difficulty(Situation::Sloth, 2.)),
+        ]);
+        let (score, clock, difficulty) = run(50, 10., 1.);
+
+        let next = milestones.next(&Reached::default(), &score, &clock, &difficulty).map(|milestone| milestone.name.as_str());
+        assert_eq!(next, Some("first"));
+    }
+}
@@ -0,0 +1,589 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::{
+    asset::{io::{file::FileAssetReader, AssetSource, Reader}, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::{BoxedFuture, HashSet},
+};
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::game::{self, BadThing, CircleCollider, DeathMessage, ExtraMessages, GameEntity, GameSetup, GoodThing, Situation};
+use crate::generic_ui::{self, spawn_button, ButtonActionType, Toggle};
+use crate::hazards::{Grow, Patrol, Rotate};
+use crate::motion::{FallAndWrap, Impulse, InvertVelocity, LinearDrift, RescuedBy, ResetTo, SeekTarget, Target, Wander};
+use crate::milestones::{Condition, MilestoneSet, Milestones};
+use crate::scripting;
+use crate::theme::UiTheme;
+use crate::util;
+
+// packs live in mods/<pack>/ next to the executable, the same way assets/ does
+pub const MODS_SOURCE: &str = "mods";
+const MANIFEST: &str = "manifest.mod.json";
+const DISABLED: &str = "disabled.json";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub situations: Vec<SituationDef>,
+    // extra game over lines for the built in situations
+    #[serde(default)]
+    pub messages: HashMap<Situation, Vec<String>>,
+}
+
+// a modded situation borrows a built in one's difficulty, buttons and owner
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SituationDef {
+    pub name: String,
+    pub track: Situation,
+    // at most one of these, with none the situation unlocks along with its track
+    #[serde(default)]
+    pub unlock_at: Option<f32>,
+    #[serde(default)]
+    pub unlock_score: Option<i32>,
+    #[serde(default)]
+    pub unlock_seconds: Option<f32>,
+    pub good: ThingDef,
+    pub bad: ThingDef,
+    #[serde(default)]
+    pub message: Option<String>,
+    // a .rhai file with on_spawn, on_tick, on_rescue and on_collision hooks
+    #[serde(default)]
+    pub script: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ThingDef {
+    pub sprite: String,
+    pub position: [f32; 2],
+    pub radius: f32,
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default)]
+    pub drift: Option<[f32; 2]>,
+    #[serde(default)]
+    pub fall: Option<FallDef>,
+    #[serde(default)]
+    pub patrol: Option<PatrolDef>,
+    #[serde(default)]
+    pub wander: Option<WanderDef>,
+    // heads for the other thing of its situation at this speed, a fire chasing the baby or the baby walking into it
+    #[serde(default)]
+    pub seek: Option<f32>,
+    #[serde(default)]
+    pub spin: Option<f32>,
+    #[serde(default)]
+    pub grow: Option<GrowDef>,
+    #[serde(default)]
+    pub rescue: RescueDef,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FallDef {
+    pub speed: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PatrolDef {
+    pub points: Vec<[f32; 2]>,
+    pub speed: f32,
+}
+
+// roams around where it spawned, turned back once it strays past radius
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WanderDef {
+    pub speed: f32,
+    pub turn_rate: f32,
+    pub radius: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrowDef {
+    pub from: f32,
+    pub to: f32,
+    pub seconds: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RescueDef {
+    #[serde(default)]
+    pub reset_x: Option<f32>,
+    #[serde(default)]
+    pub impulse: Option<[f32; 2]>,
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl Manifest {
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push("name is empty".to_string());
+        }
+
+        for situation in &self.situations {
+            let unlock_at = situation.unlock_at();
+            let earliest = game::unlock_threshold(situation.track);
+
+            if unlock_at < earliest {
+                errors.push(format!("{}: unlock_at {} is before {:?} has buttons ({})", situation.name, unlock_at, situation.track, earliest));
+            }
+
+            let unlocks = [situation.unlock_at.is_some(), situation.unlock_score.is_some(), situation.unlock_seconds.is_some()];
+
+            if unlocks.iter().filter(|set| **set).count() > 1 {
+                errors.push(format!("{}: only one of unlock_at, unlock_score and unlock_seconds can be set", situation.name));
+            }
+
+            // score and time don't wait for the track's buttons
+            if earliest > 0. && (situation.unlock_score.is_some() || situation.unlock_seconds.is_some()) {
+                errors.push(format!("{}: {:?} has no buttons until difficulty {}, so it can only use unlock_at", situation.name, situation.track, earliest));
+            }
+
+            for (which, thing) in [("good", &situation.good), ("bad", &situation.bad)] {
+                if thing.radius <= 0. {
+                    errors.push(format!("{} {}: radius must be positive", situation.name, which));
+                }
+
+                if thing.patrol.as_ref().is_some_and(|patrol| patrol.points.len() < 2) {
+                    errors.push(format!("{} {}: patrol needs at least two points", situation.name, which));
+                }
+
+                if thing.wander.as_ref().is_some_and(|wander| wander.radius <= 0.) {
+                    errors.push(format!("{} {}: wander radius must be positive", situation.name, which));
+                }
+
+                if thing.seek.is_some_and(|speed| speed <= 0.) {
+                    errors.push(format!("{} {}: seek speed must be positive", situation.name, which));
+                }
+
+                if thing.grow.as_ref().is_some_and(|grow| grow.seconds <= 0.) {
+                    errors.push(format!("{} {}: grow seconds must be positive", situation.name, which));
+                }
+            }
+        }
+
+        for (situation, lines) in &self.messages {
+            if lines.iter().any(|line| line.trim().is_empty()) {
+                errors.push(format!("messages for {:?} contain an empty line", situation));
+            }
+        }
+
+        errors
+    }
+
+    fn files(&self) -> Vec<String> {
+        let things = self.situations.iter()
+            .flat_map(|situation| [&situation.good, &situation.bad])
+            .flat_map(|thing| [Some(thing.sprite.clone()), thing.sound.clone()]);
+
+        let scripts = self.situations.iter().map(|situation| situation.script.clone());
+
+        things.chain(scripts).flatten().collect()
+    }
+}
+
+impl SituationDef {
+    fn unlock_at(&self) -> f32 {
+        self.unlock_at.unwrap_or_else(|| game::unlock_threshold(self.track))
+    }
+
+    fn unlock(&self) -> Condition {
+        match (self.unlock_score, self.unlock_seconds) {
+            (Some(score), _) => Condition::Score(score),
+            (_, Some(seconds)) => Condition::Seconds(seconds),
+            _ => Condition::Difficulty(self.track, self.unlock_at()),
+        }
+    }
+}
+
+// a broken pack still loads, carrying what is wrong with it instead of its manifest
+#[derive(Asset, TypePath, Debug)]
+pub struct ModPack {
+    pub manifest: Option<Manifest>,
+    pub errors: Vec<String>,
+}
+
+impl ModPack {
+    pub fn valid(&self) -> Option<&Manifest> {
+        self.manifest.as_ref().filter(|_| self.errors.is_empty())
+    }
+}
+
+#[derive(Default)]
+struct ModPackLoader;
+
+impl AssetLoader for ModPackLoader {
+    type Asset = ModPack;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<ModPack, std::io::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+
+            let manifest = match serde_json::from_slice::<Manifest>(&bytes) {
+                Ok(manifest) => manifest,
+                Err(error) => return Ok(ModPack { manifest: None, errors: vec![format!("{}: {}", MANIFEST, error)] }),
+            };
+
+            let mut errors = manifest.validate();
+
+            for file in manifest.files() {
+                let found = match load_context.asset_path().resolve_embed(&file) {
+                    Ok(path) => load_context.read_asset_bytes(path).await.is_ok(),
+                    Err(_) => false,
+                };
+
+                if !found {
+                    errors.push(format!("missing file {}", file));
+                }
+            }
+
+            Ok(ModPack { manifest: Some(manifest), errors })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mod.json"]
+    }
+}
+
+pub struct PackEntry {
+    pub folder: String,
+    pub handle: Handle<ModPack>,
+}
+
+#[derive(Resource, Default)]
+pub struct Packs(pub Vec<PackEntry>);
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct ModSettings {
+    pub disabled: HashSet<String>,
+}
+
+impl ModSettings {
+    fn load() -> Self {
+        fs::read_to_string(mods_directory().join(DISABLED))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let written = fs::create_dir_all(mods_directory())
+            .and_then(|_| serde_json::to_string_pretty(self).map_err(std::io::Error::from))
+            .and_then(|json| fs::write(mods_directory().join(DISABLED), json));
+
+        if let Err(error) = written {
+            warn!("could not save mod settings: {}", error);
+        }
+    }
+}
+
+// modded entities are rebuilt from their packs rather than saved
+#[derive(Component)]
+pub struct Modded;
+
+#[derive(Resource, Default)]
+struct ModRun {
+    // names of the milestones registered for the enabled packs' situations
+    milestones: Vec<String>,
+}
+
+#[derive(Component)]
+struct ModsEntity;
+
+#[derive(Component)]
+struct PackToggle(String);
+
+pub fn mods_directory() -> PathBuf {
+    FileAssetReader::get_base_path().join(MODS_SOURCE)
+}
+
+// asset sources have to exist before the asset plugin is built
+pub fn register_source(app: &mut App) {
+    app.register_asset_source(MODS_SOURCE, AssetSource::build()
+        .with_reader(AssetSource::get_default_reader(MODS_SOURCE.into()))
+        .with_watcher(AssetSource::get_default_watcher(MODS_SOURCE.into(), Duration::from_millis(300)))
+    );
+}
+
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_asset::<ModPack>()
+        .init_asset_loader::<ModPackLoader>()
+        .init_resource::<Packs>()
+        .init_resource::<ModRun>()
+        .insert_resource(ModSettings::load())
+        .add_systems(Startup, scan_mods)
+        .add_systems(OnEnter(AppState::Game), collect_messages.before(GameSetup))
+        .add_systems(Update, register_mod_milestones
+            .before(MilestoneSet)
+            .run_if(|mut events: EventReader<AssetEvent<ModPack>>, settings: Res<ModSettings>| events.read().count() > 0 || settings.is_changed())
+        )
+        .add_systems(Update, (show_mods, toggle_packs).run_if(in_state(AppState::Mods)))
+        .add_systems(OnExit(AppState::Mods), util::cleanup_system::<ModsEntity>);
+    }
+}
+
+fn scan_mods(mut packs: ResMut<Packs>, asset_server: Res<AssetServer>) {
+    let Ok(entries) = fs::read_dir(mods_directory()) else {
+        return;
+    };
+
+    let mut folders: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().join(MANIFEST).is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    folders.sort();
+
+    packs.0 = folders.into_iter()
+        .map(|folder| PackEntry {
+            handle: asset_server.load(format!("{}://{}/{}", MODS_SOURCE, folder, MANIFEST)),
+            folder,
+        })
+        .collect();
+}
+
+fn enabled<'a>(packs: &'a Packs, settings: &'a ModSettings, assets: &'a Assets<ModPack>) -> impl Iterator<Item = (&'a str, &'a Manifest)> {
+    packs.0.iter()
+        .filter(|pack| !settings.disabled.contains(&pack.folder))
+        .filter_map(|pack| Some((pack.folder.as_str(), assets.get(&pack.handle)?.valid()?)))
+}
+
+fn collect_messages(
+    mut messages: ResMut<ExtraMessages>,
+    packs: Res<Packs>,
+    settings: Res<ModSettings>,
+    assets: Res<Assets<ModPack>>,
+) {
+    messages.0.clear();
+
+    for (_, manifest) in enabled(&packs, &settings, &assets) {
+        for (situation, lines) in &manifest.messages {
+            messages.0.entry(*situation).or_default().extend(lines.iter().cloned());
+        }
+    }
+}
+
+fn spawn_thing(commands: &mut Commands, asset_server: &AssetServer, folder: &str, track: Situation, thing: &ThingDef) -> Entity {
+    let file = |name: &str| format!("{}://{}/{}", MODS_SOURCE, folder, name);
+    let position = Vec2::from_array(thing.position);
+
+    let mut entity = commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_translation(position.extend(0.8)),
+            texture: asset_server.load(file(&thing.sprite)),
+            ..default()
+        },
+        GameEntity,
+        Modded,
+        CircleCollider { radius: thing.radius },
+        RescuedBy(track),
+    ));
+
+    if let Some(sound) = &thing.sound {
+        entity.insert(AudioBundle {
+            source: asset_server.load(file(sound)),
+            settings: PlaybackSettings {
+                mode: bevy::audio::PlaybackMode::Loop,
+                ..default()
+            },
+        });
+    }
+
+    if let Some(drift) = thing.drift {
+        entity.insert(LinearDrift { velocity: Vec2::from_array(drift), scale_by_difficulty: Some(track) });
+    }
+
+    if let Some(fall) = &thing.fall {
+        entity.insert(FallAndWrap { speed: fall.speed, top: fall.top, bottom: fall.bottom });
+    }
+
+    if let Some(patrol) = &thing.patrol {
+        entity.insert(Patrol { points: patrol.points.iter().copied().map(Vec2::from_array).collect(), speed: patrol.speed, phase: 0. });
+    }
+
+    if let Some(wander) = &thing.wander {
+        entity.insert(Wander { speed: wander.speed, turn_rate: wander.turn_rate, home: position, radius: wander.radius, heading: 0. });
+    }
+
+    if let Some(speed) = thing.spin {
+        entity.insert(Rotate { speed, direction: 1., scale_by: Some(track) });
+    }
+
+    if let Some(grow) = &thing.grow {
+        entity.insert(Grow { from: grow.from, to: grow.to, seconds: grow.seconds, phase: 0., ping_pong: true });
+    }
+
+    if let Some(x) = thing.rescue.reset_x {
+        entity.insert(ResetTo { x: Some(x), y: None });
+    }
+
+    if let Some(offset) = thing.rescue.impulse {
+        entity.insert(Impulse { offset: Vec2::from_array(offset), seconds: 0.25 });
+    }
+
+    if thing.rescue.invert {
+        entity.insert(InvertVelocity);
+    }
+
+    entity.id()
+}
+
+fn spawn_situation(commands: &mut Commands, asset_server: &AssetServer, folder: &str, situation: &SituationDef) {
+    let good = spawn_thing(commands, asset_server, folder, situation.track, &situation.good);
+    commands.entity(good).insert(GoodThing { situation: situation.track });
+
+    if let Some(message) = &situation.message {
+        commands.entity(good).insert(DeathMessage(message.clone()));
+    }
+
+    let bad = spawn_thing(commands, asset_server, folder, situation.track, &situation.bad);
+    commands.entity(bad).insert(BadThing);
+
+    // each side can only be pointed at the other once both exist
+    for (thing, entity, other) in [(&situation.good, good, bad), (&situation.bad, bad, good)] {
+        if let Some(speed) = thing.seek {
+            commands.entity(entity).insert(SeekTarget { target: Target::Entity(other), speed });
+        }
+    }
+
+    if let Some(script) = &situation.script {
+        scripting::attach(commands, asset_server, folder, situation.track, script, good, bad);
+    }
+}
+
+// every enabled pack's situations unlock as milestones, registered again whenever a pack loads, changes or is toggled
+fn register_mod_milestones(world: &mut World) {
+    let registered = std::mem::take(&mut world.resource_mut::<ModRun>().milestones);
+
+    for name in registered {
+        if let Some(handler) = world.resource_mut::<Milestones>().remove(&name) {
+            let _ = world.remove_system(handler);
+        }
+    }
+
+    let situations: Vec<(String, SituationDef)> = {
+        let (packs, settings, assets) = (world.resource::<Packs>(), world.resource::<ModSettings>(), world.resource::<Assets<ModPack>>());
+
+        enabled(packs, settings, assets)
+            .flat_map(|(folder, manifest)| manifest.situations.iter().map(move |situation| (folder.to_string(), situation.clone())))
+            .collect()
+    };
+
+    for (folder, situation) in situations {
+        // the pack's folder keeps two packs' situations with the same name apart
+        let name = format!("{}/{}", folder, situation.name);
+        let condition = situation.unlock();
+
+        // the track's own unlock has already been announced, so this only spawns
+        let handler = world.register_system(move |mut commands: Commands, asset_server: Res<AssetServer>| {
+            spawn_situation(&mut commands, &asset_server, &folder, &situation);
+        });
+
+        world.resource_mut::<Milestones>().add(name.clone(), condition, handler);
+        world.resource_mut::<ModRun>().milestones.push(name);
+    }
+}
+
+// rebuilt whenever a pack finishes loading or changes on disk
+fn show_mods(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<ModPack>>,
+    existing: Query<Entity, With<ModsEntity>>,
+    theme: Res<UiTheme>,
+    packs: Res<Packs>,
+    settings: Res<ModSettings>,
+    assets: Res<Assets<ModPack>>,
+) {
+    if events.read().count() == 0 && !existing.is_empty() {
+        return;
+    }
+
+    for entity in &existing {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands
+        .spawn((generic_ui::screen(), ModsEntity))
+        .with_children(|parent| {
+            generic_ui::title(parent, &theme, "mods");
+
+            if packs.0.is_empty() {
+                generic_ui::label(parent, &theme, format!("no packs found in {}", mods_directory().display()));
+            }
+
+            for pack in &packs.0 {
+                match assets.get(&pack.handle) {
+                    None => {
+                        generic_ui::text(parent, &theme, format!("{}: loading", pack.folder), theme.small_text_size, theme.text);
+                    },
+                    Some(loaded) => match loaded.valid() {
+                        Some(manifest) => {
+                            let on = !settings.disabled.contains(&pack.folder);
+                            generic_ui::toggle(parent, &theme, &manifest.name, on).insert(PackToggle(pack.folder.clone()));
+
+                            if !manifest.description.is_empty() {
+                                generic_ui::text(parent, &theme, &manifest.description, theme.small_text_size, theme.text);
+                            }
+                        },
+                        None => {
+                            generic_ui::text(parent, &theme, format!("{} is broken", pack.folder), theme.small_text_size, theme.danger);
+
+                            for error in &loaded.errors {
+                                generic_ui::text(parent, &theme, error, theme.small_text_size * 0.75, theme.danger);
+                            }
+                        },
+                    },
+                }
+            }
+
+            spawn_button(parent, &theme, "back", ButtonActionType::ChangeAppState(AppState::MainMenu));
+        });
+}
+
+fn toggle_packs(toggles: Query<(&Toggle, &PackToggle), Changed<Toggle>>, mut settings: ResMut<ModSettings>) {
+    let mut changed = false;
+
+    for (toggle, pack) in &toggles {
+        if toggle.on == settings.disabled.contains(&pack.0) {
+            match toggle.on {
+                true => settings.disabled.remove(&pack.0),
+                false => settings.disabled.insert(pack.0.clone()),
+            };
+            changed = true;
+        }
+    }
+
+    if changed {
+        settings.save();
+    }
+}
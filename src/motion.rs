@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use rand::Rng;
+use crate::app_state::AppState;
+use crate::game::{Difficulty, GameRng, Rescue, Situation};
+use crate::hazards::Rotate;
+use crate::pause;
+use crate::tween::{self, Ease, Lens, Tween};
+
+#[derive(Component, Debug, Clone)]
+pub struct LinearDrift {
+    pub velocity: Vec2,
+    pub scale_by_difficulty: Option<Situation>,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct FallAndWrap {
+    pub speed: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Wander {
+    pub speed: f32,
+    pub turn_rate: f32,
+    pub home: Vec2,
+    pub radius: f32,
+    pub heading: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    Point(Vec2),
+    Entity(Entity),
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct SeekTarget {
+    pub target: Target,
+    pub speed: f32,
+}
+
+// rescue effects fire on every entity whose RescuedBy matches the rescued situation
+#[derive(Component, Debug, Clone, Copy)]
+pub struct RescuedBy(pub Situation);
+
+#[derive(Component, Debug, Clone)]
+pub struct ResetTo {
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Impulse {
+    pub offset: Vec2,
+    pub seconds: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct InvertVelocity;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MotionSet;
+
+pub struct MotionPlugin;
+
+impl Plugin for MotionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+            apply_rescue_effects,
+            (linear_drift, fall_and_wrap, wander, seek_target),
+        )
+            .chain()
+            .in_set(MotionSet)
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        );
+    }
+}
+
+fn linear_drift(mut query: Query<(&mut Transform, &LinearDrift)>, time: Res<Time>, difficulty: Res<Difficulty>) {
+    for (mut transform, drift) in &mut query {
+        let scale = drift.scale_by_difficulty.map(|situation| difficulty.of(situation)).unwrap_or(1.);
+        transform.translation += (drift.velocity * scale * time.delta_seconds()).extend(0.);
+    }
+}
+
+fn fall_and_wrap(mut query: Query<(&mut Transform, &FallAndWrap)>, time: Res<Time>) {
+    for (mut transform, fall) in &mut query {
+        transform.translation.y -= fall.speed * time.delta_seconds();
+
+        if transform.translation.y < fall.bottom {
+            transform.translation.y = fall.top;
+        }
+    }
+}
+
+fn wander(mut query: Query<(&mut Transform, &mut Wander)>, time: Res<Time>, mut rng: ResMut<GameRng>) {
+    for (mut transform, mut wander) in &mut query {
+        let position = transform.translation.xy();
+
+        wander.heading += rng.0.gen_range(-1.0..1.0) * wander.turn_rate * time.delta_seconds();
+
+        if position.distance(wander.home) > wander.radius {
+            let home = wander.home - position;
+            wander.heading = home.y.atan2(home.x);
+        }
+
+        let step = Vec2::from_angle(wander.heading) * wander.speed * time.delta_seconds();
+        transform.translation += step.extend(0.);
+    }
+}
+
+fn seek_target(mut query: Query<(&mut Transform, &SeekTarget)>, targets: Query<&GlobalTransform>, time: Res<Time>) {
+    for (mut transform, seek) in &mut query {
+        let target = match seek.target {
+            Target::Point(point) => point,
+            Target::Entity(entity) => match targets.get(entity) {
+                Ok(target) => target.translation().xy(),
+                Err(_) => continue,
+            },
+        };
+
+        let position = transform.translation.xy();
+        let step = (target - position).clamp_length_max(seek.speed * time.delta_seconds());
+        transform.translation += step.extend(0.);
+    }
+}
+
+type RescueEffects<'a> = (
+    Entity,
+    &'a RescuedBy,
+    &'a mut Transform,
+    Option<&'a ResetTo>,
+    Option<&'a Impulse>,
+    Has<InvertVelocity>,
+    Option<&'a mut LinearDrift>,
+    Option<&'a mut Rotate>,
+);
+
+fn apply_rescue_effects(
+    mut commands: Commands,
+    mut rescues: EventReader<Rescue>,
+    mut query: Query<RescueEffects>,
+) {
+    for rescue in rescues.read() {
+        for (entity, rescued_by, mut transform, reset, impulse, invert, drift, rotate) in &mut query {
+            if rescued_by.0 != rescue.situation {
+                continue;
+            }
+
+            if let Some(reset) = reset {
+                transform.translation.x = reset.x.unwrap_or(transform.translation.x);
+                transform.translation.y = reset.y.unwrap_or(transform.translation.y);
+            }
+
+            if let Some(impulse) = impulse {
+                let push = Tween::new(Lens::TranslateBy(impulse.offset.extend(0.)), impulse.seconds, Ease::QuadOut);
+                commands.add(tween::tween(entity, push));
+            }
+
+            if invert {
+                if let Some(mut drift) = drift {
+                    drift.velocity = -drift.velocity;
+                }
+
+                if let Some(mut rotate) = rotate {
+                    rotate.direction = -rotate.direction;
+                }
+            }
+        }
+    }
+}
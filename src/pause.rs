@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use crate::app_state::AppState;
+use crate::generic_ui::{self, spawn_button, ButtonActionType};
+use crate::save::SaveActions;
+use crate::theme::UiTheme;
+use crate::util as util;
+
+#[derive(Resource, Default)]
+pub struct Paused(pub bool);
+
+// gameplay held still without the pause menu, e.g. while the camera lingers on a collision
+#[derive(Resource, Default)]
+pub struct Frozen(pub bool);
+
+pub fn running(paused: Res<Paused>, frozen: Res<Frozen>) -> bool {
+    !paused.0 && !frozen.0
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Paused>()
+        .init_resource::<Frozen>()
+        .add_systems(Update, (toggle_pause.run_if(|frozen: Res<Frozen>| !frozen.0), show_pause).chain().run_if(in_state(AppState::Game)))
+        .add_systems(OnExit(AppState::Game), (unpause, util::cleanup_system::<PauseEntity>));
+
+        #[cfg(feature = "debug")]
+        {
+            use crate::console::{self, ConsoleExt};
+
+            app.console_command("timescale", "timescale 0.25 runs the game at a quarter speed", &["0.25", "0.5", "1", "2"], |world, args| {
+                let scale: f32 = console::parse_arg(args, 0, "scale")?;
+
+                if scale <= 0. {
+                    return Err("timescale must be above 0, use pause instead".into());
+                }
+
+                world.resource_mut::<Time<Virtual>>().set_relative_speed(scale);
+                Ok(format!("timescale {}", scale))
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+struct PauseEntity;
+
+fn toggle_pause(keys: Res<Input<KeyCode>>, mut paused: ResMut<Paused>) {
+    if keys.just_pressed(KeyCode::Escape) {
+        paused.0 = !paused.0;
+    }
+}
+
+fn show_pause(
+    mut commands: Commands,
+    theme: Res<UiTheme>,
+    paused: Res<Paused>,
+    save_actions: Res<SaveActions>,
+    mut time: ResMut<Time<Virtual>>,
+    overlay: Query<Entity, With<PauseEntity>>,
+) {
+    if !paused.is_changed() {
+        return;
+    }
+
+    for e in overlay.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    if !paused.0 {
+        time.unpause();
+        return;
+    }
+
+    time.pause();
+
+    generic_ui::modal(&mut commands, &theme, PauseEntity, |parent| {
+        generic_ui::title(parent, &theme, "paused");
+
+        spawn_button(parent, &theme, "resume", ButtonActionType::callback(|world| world.resource_mut::<Paused>().0 = false));
+
+        spawn_button(parent, &theme, "save & quit", ButtonActionType::Run(save_actions.save_and_quit));
+    });
+}
+
+fn unpause(mut paused: ResMut<Paused>, mut time: ResMut<Time<Virtual>>) {
+    paused.0 = false;
+    time.unpause();
+}
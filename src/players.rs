@@ -0,0 +1,111 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use crate::game::Situation;
+
+#[derive(Resource, Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    #[default]
+    Solo,
+    Coop,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    pub fn index(&self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Player::One => "player 1",
+            Player::Two => "player 2",
+        }
+    }
+
+    // every player has a key for every situation so any split of the roster works
+    pub fn rescue_key(&self, situation: Situation) -> KeyCode {
+        match (self, situation) {
+            (Player::One, Situation::Baby) => KeyCode::Q,
+            (Player::One, Situation::Sloth) => KeyCode::W,
+            (Player::One, Situation::Kitten) => KeyCode::E,
+            (Player::Two, Situation::Baby) => KeyCode::I,
+            (Player::Two, Situation::Sloth) => KeyCode::O,
+            (Player::Two, Situation::Kitten) => KeyCode::P,
+        }
+    }
+
+    pub fn end_key(&self, situation: Situation) -> KeyCode {
+        match (self, situation) {
+            (Player::One, Situation::Baby) => KeyCode::A,
+            (Player::One, Situation::Sloth) => KeyCode::S,
+            (Player::One, Situation::Kitten) => KeyCode::D,
+            (Player::Two, Situation::Baby) => KeyCode::K,
+            (Player::Two, Situation::Sloth) => KeyCode::L,
+            (Player::Two, Situation::Kitten) => KeyCode::Semicolon,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Roster {
+    pub mode: GameMode,
+    owners: HashMap<Situation, Player>,
+}
+
+impl Roster {
+    pub fn for_mode(mode: GameMode) -> Self {
+        let mut owners = HashMap::new();
+        owners.insert(Situation::Baby, Player::One);
+        owners.insert(Situation::Sloth, Player::One);
+
+        match mode {
+            GameMode::Solo => owners.insert(Situation::Kitten, Player::One),
+            GameMode::Coop => owners.insert(Situation::Kitten, Player::Two),
+        };
+
+        Roster { mode, owners }
+    }
+
+    pub fn owner(&self, situation: Situation) -> Player {
+        self.owners.get(&situation).copied().unwrap_or(Player::One)
+    }
+
+    pub fn players(&self) -> &'static [Player] {
+        match self.mode {
+            GameMode::Solo => &[Player::One],
+            GameMode::Coop => &[Player::One, Player::Two],
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PlayerScores {
+    pub points: [i32; 2],
+    pub blamed: Option<Player>,
+}
+
+#[derive(Component)]
+pub struct Owner(pub Player);
+
+#[derive(Component)]
+pub struct Hotkey(pub KeyCode);
+
+pub struct PlayersPlugin;
+
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<GameMode>()
+        .init_resource::<PlayerScores>()
+        .insert_resource(Roster::for_mode(GameMode::Solo));
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::{TimeSystem, TimeUpdateStrategy}};
+use crate::app_state::AppState;
+use crate::game::{Rescue, RescueCooldown, Situation, SituationRetired};
+use crate::generic_ui::Cooldown;
+use crate::pause;
+use crate::stamina::Stamina;
+use crate::telemetry::RecordedRun;
+
+// steps through a telemetry log frame by frame, with the frame lengths it was recorded at, pressing its rescues and end buttons on the frames they were pressed
+#[derive(Resource)]
+pub struct Replay {
+    deltas: Vec<f32>,
+    rescues: Vec<(u32, Situation)>,
+    retirements: Vec<(u32, Situation)>,
+    frame: u32,
+    next: usize,
+    next_retirement: usize,
+    // how time was stepped before the replay took over, put back once it's done
+    previous: Option<TimeUpdateStrategy>,
+}
+
+impl Replay {
+    pub fn new(run: &RecordedRun) -> Self {
+        Replay {
+            deltas: run.deltas.clone(),
+            rescues: run.rescues.clone(),
+            retirements: run.retirements.clone(),
+            frame: 0,
+            next: 0,
+            next_retirement: 0,
+            previous: None,
+        }
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        // the first frame of the run has to be stepped before its state change is applied
+        .add_systems(First, step_time
+            .before(TimeSystem)
+            .run_if(resource_exists::<Replay>())
+            .run_if(|state: Res<State<AppState>>, next: Res<NextState<AppState>>| *state.get() == AppState::Game || next.0 == Some(AppState::Game))
+        )
+        .add_systems(Update, play_back
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+            .run_if(resource_exists::<Replay>())
+        )
+        .add_systems(OnExit(AppState::Game), rewind);
+    }
+}
+
+fn step_time(mut replay: ResMut<Replay>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let Some(delta) = replay.deltas.get(replay.frame as usize).copied() else {
+        // past the end of the recording, the run carries on in real time
+        if let Some(previous) = replay.previous.take() {
+            *strategy = previous;
+        }
+
+        return;
+    };
+
+    let stepped = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(delta));
+    let previous = std::mem::replace(&mut *strategy, stepped);
+
+    replay.previous.get_or_insert(previous);
+}
+
+fn rewind(replay: Option<ResMut<Replay>>, mut strategy: ResMut<TimeUpdateStrategy>) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+
+    replay.frame = 0;
+    replay.next = 0;
+    replay.next_retirement = 0;
+
+    if let Some(previous) = replay.previous.take() {
+        *strategy = previous;
+    }
+}
+
+fn play_back(
+    mut replay: ResMut<Replay>,
+    mut rescues: EventWriter<Rescue>,
+    mut retirements: EventWriter<SituationRetired>,
+    mut stamina: ResMut<Stamina>,
+    mut cooldowns: Query<(&RescueCooldown, &mut Cooldown)>,
+) {
+    // counted the same way telemetry counts them, running frames only
+    replay.frame += 1;
+
+    while let Some((frame, situation)) = replay.rescues.get(replay.next).copied() {
+        if frame > replay.frame {
+            break;
+        }
+
+        replay.next += 1;
+
+        // spend what the original press spent, so stamina and charges line up with the recording
+        stamina.spend(situation);
+
+        if let Some((_, mut cooldown)) = cooldowns.iter_mut().find(|(rescue, _)| rescue.situation == situation) {
+            cooldown.take();
+        }
+
+        rescues.send(Rescue { situation });
+    }
+
+    while let Some((frame, situation)) = replay.retirements.get(replay.next_retirement).copied() {
+        if frame > replay.frame {
+            break;
+        }
+
+        replay.next_retirement += 1;
+        retirements.send(SituationRetired(situation));
+    }
+}
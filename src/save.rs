@@ -0,0 +1,306 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::{ecs::system::SystemId, prelude::*, window::WindowCloseRequested};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::combo::ScoreBreakdown;
+use crate::game::{BadThing, Carousel, CollisionSet, Difficulty, DifficultyProfile, GameEntity, GameRng, GameSetup, GoodThing, Mallet, Retired, RunClock, RunSeed, Score, ScoreTimer, Situation};
+use crate::hazards::Rotate;
+use crate::milestones::MilestoneSet;
+use crate::mods::Modded;
+use crate::pause::Frozen;
+use crate::players::{GameMode, PlayerScores};
+use crate::stamina::Stamina;
+
+const SAVE_VERSION: u32 = 6;
+
+#[derive(Resource)]
+pub struct SaveSettings {
+    pub path: PathBuf,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        SaveSettings {
+            path: PathBuf::from("save.json"),
+        }
+    }
+}
+
+impl SaveSettings {
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+}
+
+#[derive(Event)]
+pub struct SaveGame;
+
+#[derive(Resource)]
+pub struct SaveActions {
+    pub continue_run: SystemId,
+    pub save_and_quit: SystemId,
+}
+
+impl FromWorld for SaveActions {
+    fn from_world(world: &mut World) -> Self {
+        SaveActions {
+            continue_run: world.register_system(continue_run),
+            save_and_quit: world.register_system(save_and_quit),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum SavedKind {
+    Good(Situation),
+    Carousel,
+    Fire,
+    Mallet,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SavedEntity {
+    kind: SavedKind,
+    translation: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+    direction: Option<f32>,
+}
+
+fn kind_of(good_thing: Option<&GoodThing>, carousel: bool, bad: bool, mallet: bool) -> Option<SavedKind> {
+    match (good_thing, carousel, bad, mallet) {
+        (Some(good_thing), _, _, _) => Some(SavedKind::Good(good_thing.situation)),
+        (_, true, _, _) => Some(SavedKind::Carousel),
+        (_, _, false, true) => Some(SavedKind::Mallet),
+        (_, _, true, false) => Some(SavedKind::Fire),
+        // the mallet's collider window follows the run clock
+        _ => None,
+    }
+}
+
+impl SavedEntity {
+    fn transform(&self) -> Transform {
+        Transform {
+            translation: Vec3::from_array(self.translation),
+            rotation: Quat::from_array(self.rotation),
+            scale: Vec3::from_array(self.scale),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SaveData {
+    version: u32,
+    mode: GameMode,
+    profile: DifficultyProfile,
+    difficulty: Difficulty,
+    score: i32,
+    player_points: [i32; 2],
+    score_timer_elapsed: f32,
+    stamina: f32,
+    breakdown: ScoreBreakdown,
+    clock: f32,
+    // the seed the run started from, so its telemetry log still points at the right one
+    seed: u64,
+    rng: ChaCha8Rng,
+    // situations ended from their buttons, kept out of the run when milestones rebuild it
+    retired: Vec<Situation>,
+    entities: Vec<SavedEntity>,
+}
+
+#[derive(Resource, Default)]
+struct PendingResume(Option<SaveData>);
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<SaveSettings>()
+        .init_resource::<PendingResume>()
+        .add_event::<SaveGame>()
+        .init_resource::<SaveActions>()
+        .add_systems(PostUpdate, (save_on_close, save_game)
+            .chain()
+            .after(CollisionSet)
+            .run_if(in_state(AppState::Game))
+            .run_if(still_running)
+        )
+        // milestones rebuild whatever the restored difficulty had unlocked, then the transforms are put back on top
+        .add_systems(OnEnter(AppState::Game), restore_run.after(GameSetup).before(MilestoneSet))
+        .add_systems(OnEnter(AppState::Game), (apply_deferred, restore_transforms).chain().after(MilestoneSet));
+    }
+}
+
+// a run that has just been lost, or is playing its finale, isn't worth resuming
+fn still_running(frozen: Res<Frozen>, next_state: Res<NextState<AppState>>) -> bool {
+    !frozen.0 && next_state.0 != Some(AppState::GameOver)
+}
+
+fn save_on_close(mut close_requests: EventReader<WindowCloseRequested>, mut saves: EventWriter<SaveGame>) {
+    if close_requests.read().count() > 0 {
+        saves.send(SaveGame);
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn save_game(
+    mut saves: EventReader<SaveGame>,
+    settings: Res<SaveSettings>,
+    mode: Res<GameMode>,
+    profile: Res<DifficultyProfile>,
+    difficulty: Res<Difficulty>,
+    score: Res<Score>,
+    player_scores: Res<PlayerScores>,
+    timer: Res<ScoreTimer>,
+    stamina: Res<Stamina>,
+    breakdown: Res<ScoreBreakdown>,
+    clock: Res<RunClock>,
+    seed: Res<RunSeed>,
+    rng: Res<GameRng>,
+    retired: Res<Retired>,
+    entities: Query<(&Transform, Option<&GoodThing>, Option<&Rotate>, Has<Carousel>, Has<BadThing>, Has<Mallet>), (With<GameEntity>, Without<Modded>)>,
+) {
+    if saves.read().count() == 0 {
+        return;
+    }
+
+    let entities = entities.iter()
+        .filter_map(|(transform, good_thing, rotate, carousel, bad, mallet)| {
+            let kind = kind_of(good_thing, carousel, bad, mallet)?;
+
+            Some(SavedEntity {
+                kind,
+                translation: transform.translation.to_array(),
+                rotation: transform.rotation.to_array(),
+                scale: transform.scale.to_array(),
+                direction: rotate.map(|rotate| rotate.direction),
+            })
+        })
+        .collect();
+
+    let data = SaveData {
+        version: SAVE_VERSION,
+        mode: *mode,
+        profile: profile.clone(),
+        difficulty: difficulty.clone(),
+        score: score.0,
+        player_points: player_scores.points,
+        score_timer_elapsed: timer.0.elapsed_secs(),
+        stamina: stamina.current,
+        breakdown: breakdown.clone(),
+        clock: clock.0.elapsed_secs(),
+        seed: seed.0,
+        rng: rng.0.clone(),
+        retired: retired.0.iter().copied().collect(),
+        entities,
+    };
+
+    let written = serde_json::to_string_pretty(&data)
+        .map_err(std::io::Error::from)
+        .and_then(|json| fs::write(&settings.path, json));
+
+    match written {
+        Ok(_) => info!("saved run to {}", settings.path.display()),
+        Err(error) => warn!("could not save run to {}: {}", settings.path.display(), error),
+    }
+}
+
+fn save_and_quit(mut saves: EventWriter<SaveGame>, mut next_state: ResMut<NextState<AppState>>) {
+    saves.send(SaveGame);
+    next_state.set(AppState::MainMenu);
+}
+
+fn continue_run(
+    settings: Res<SaveSettings>,
+    mut pending: ResMut<PendingResume>,
+    mut mode: ResMut<GameMode>,
+    mut profile: ResMut<DifficultyProfile>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let loaded = fs::read_to_string(&settings.path)
+        .map_err(|error| error.to_string())
+        .and_then(|json| serde_json::from_str::<SaveData>(&json).map_err(|error| error.to_string()))
+        .and_then(|data| match data.version {
+            SAVE_VERSION => Ok(data),
+            version => Err(format!("save version {} is not supported (expected {})", version, SAVE_VERSION)),
+        });
+
+    match loaded {
+        Ok(data) => {
+            *mode = data.mode;
+            *profile = data.profile.clone();
+            pending.0 = Some(data);
+            next_state.set(AppState::Game);
+
+            if let Err(error) = fs::remove_file(&settings.path) {
+                warn!("could not remove save {}: {}", settings.path.display(), error);
+            }
+        },
+        Err(error) => warn!("could not load {}: {}", settings.path.display(), error),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restore_run(
+    mut commands: Commands,
+    pending: Res<PendingResume>,
+    mut player_scores: ResMut<PlayerScores>,
+    mut clock: ResMut<RunClock>,
+    mut seed: ResMut<RunSeed>,
+    mut rng: ResMut<GameRng>,
+    mut stamina: ResMut<Stamina>,
+    mut breakdown: ResMut<ScoreBreakdown>,
+) {
+    let Some(data) = &pending.0 else {
+        return;
+    };
+
+    commands.insert_resource(data.difficulty.clone());
+    commands.insert_resource(Retired(data.retired.iter().copied().collect()));
+    commands.insert_resource(Score(data.score, "".into()));
+
+    let mut timer = Timer::from_seconds(data.profile.tick_seconds, TimerMode::Repeating);
+    timer.set_elapsed(std::time::Duration::from_secs_f32(data.score_timer_elapsed));
+    commands.insert_resource(ScoreTimer(timer));
+
+    player_scores.points = data.player_points;
+    clock.0.set_elapsed(std::time::Duration::from_secs_f32(data.clock));
+    seed.0 = data.seed;
+    rng.0 = data.rng.clone();
+    stamina.current = data.stamina;
+    *breakdown = data.breakdown.clone();
+}
+
+#[allow(clippy::type_complexity)]
+fn restore_transforms(
+    mut pending: ResMut<PendingResume>,
+    mut entities: Query<(&mut Transform, Option<&GoodThing>, Option<&mut Rotate>, Has<Carousel>, Has<BadThing>, Has<Mallet>), (With<GameEntity>, Without<Modded>)>,
+) {
+    let Some(data) = pending.0.take() else {
+        return;
+    };
+
+    let mut saved = data.entities;
+
+    for (mut transform, good_thing, rotate, carousel, bad, mallet) in &mut entities {
+        let Some(kind) = kind_of(good_thing, carousel, bad, mallet) else {
+            continue;
+        };
+
+        let Some(index) = saved.iter().position(|entity| entity.kind == kind) else {
+            continue;
+        };
+        let entity = saved.swap_remove(index);
+
+        *transform = entity.transform();
+
+        if let (Some(mut rotate), Some(direction)) = (rotate, entity.direction) {
+            rotate.direction = direction;
+        }
+    }
+}
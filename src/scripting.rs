@@ -0,0 +1,435 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    transform::TransformSystem,
+    utils::BoxedFuture,
+};
+use rhai::{module_resolvers::DummyModuleResolver, Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST, FLOAT, INT};
+use crate::app_state::AppState;
+use crate::game::{BadThing, CircleCollider, Collision, CollisionSet, Difficulty, GameEntity, Rescue, Situation};
+use crate::mods::{Modded, MODS_SOURCE};
+use crate::pause;
+use crate::util;
+
+// keeps a runaway script from hanging the frame
+const MAX_OPERATIONS: u64 = 100_000;
+// spawns a situation can have alive at once, past this spawn does nothing until despawn makes room
+const MAX_SPAWNED: usize = 64;
+
+// the same for compiling and running, some of the limits only apply while parsing
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_print(|text| info!("script: {}", text));
+    engine.on_debug(|text, source, position| debug!("script {:?} {}: {}", source, position, text));
+
+    engine
+}
+
+// a compiled .rhai file, recompiled by the asset server whenever it changes on disk
+#[derive(Asset, TypePath)]
+pub struct Script {
+    pub ast: AST,
+}
+
+#[derive(Default)]
+struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    type Asset = Script;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Script, std::io::Error>> {
+        Box::pin(async move {
+            let mut source = String::new();
+            reader.read_to_string(&mut source).await?;
+
+            let ast = sandboxed_engine().compile(source)
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string()))?;
+
+            Ok(Script { ast })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+struct ScriptSpawn {
+    sprite: String,
+    position: Vec2,
+    // hazards collide with the situation's good thing, anything else is decoration
+    radius: Option<f32>,
+}
+
+#[derive(Resource)]
+struct Scripting {
+    engine: Engine,
+    spawns: Arc<Mutex<Vec<ScriptSpawn>>>,
+    // indices into this.spawned, as the hooks saw it
+    despawns: Arc<Mutex<Vec<INT>>>,
+}
+
+impl Default for Scripting {
+    fn default() -> Self {
+        let spawns: Arc<Mutex<Vec<ScriptSpawn>>> = default();
+        let despawns: Arc<Mutex<Vec<INT>>> = default();
+        let mut engine = sandboxed_engine();
+
+        let queue = spawns.clone();
+        engine.register_fn("spawn", move |sprite: &str, x: FLOAT, y: FLOAT| {
+            queue.lock().unwrap().push(ScriptSpawn { sprite: sprite.to_string(), position: Vec2::new(x as f32, y as f32), radius: None });
+        });
+
+        let queue = spawns.clone();
+        engine.register_fn("spawn_hazard", move |sprite: &str, x: FLOAT, y: FLOAT, radius: FLOAT| {
+            queue.lock().unwrap().push(ScriptSpawn { sprite: sprite.to_string(), position: Vec2::new(x as f32, y as f32), radius: Some(radius as f32) });
+        });
+
+        let queue = despawns.clone();
+        engine.register_fn("despawn", move |index: INT| {
+            queue.lock().unwrap().push(index);
+        });
+
+        Scripting { engine, spawns, despawns }
+    }
+}
+
+// the velocity a script has given an entity, applied every frame
+#[derive(Component, Default)]
+pub struct ScriptMotion {
+    pub velocity: Vec2,
+}
+
+// scripts only ever see copies of these entities' position, rotation and velocity, as
+// this.good, this.bad and this.spawned, plus a this.state map they can keep anything in
+#[derive(Component)]
+pub struct ScriptedSituation {
+    folder: String,
+    situation: Situation,
+    script: Handle<Script>,
+    good: Entity,
+    bad: Entity,
+    spawned: Vec<Entity>,
+    // warned about hitting MAX_SPAWNED, so a script spawning every tick doesn't flood the log
+    full: bool,
+    state: Dynamic,
+    started: bool,
+    // a script that errored sits out until it is edited
+    broken: bool,
+}
+
+pub fn attach(commands: &mut Commands, asset_server: &AssetServer, folder: &str, situation: Situation, script: &str, good: Entity, bad: Entity) {
+    commands.entity(good).insert(ScriptMotion::default());
+    commands.entity(bad).insert(ScriptMotion::default());
+
+    commands.spawn((
+        ScriptedSituation {
+            folder: folder.to_string(),
+            situation,
+            script: asset_server.load(format!("{}://{}/{}", MODS_SOURCE, folder, script)),
+            good,
+            bad,
+            spawned: Vec::new(),
+            full: false,
+            state: Dynamic::from_map(Map::new()),
+            started: false,
+            broken: false,
+        },
+        GameEntity,
+        Modded,
+    ));
+}
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_asset::<Script>()
+        .init_asset_loader::<ScriptLoader>()
+        .init_resource::<Scripting>()
+        // after every Update system has sent its rescues, and before this frame's transforms are propagated
+        .add_systems(PostUpdate, (run_scripts, move_scripted)
+            .chain()
+            .before(TransformSystem::TransformPropagate)
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        )
+        // not gated on pause, the finale freezes the run in the same frame as the collision
+        .add_systems(PostUpdate, run_collision_scripts
+            .after(CollisionSet)
+            .run_if(in_state(AppState::Game))
+        )
+        .add_systems(Update, reload_scripts);
+    }
+}
+
+fn view(entity: Entity, things: &Query<(&mut Transform, &mut ScriptMotion)>) -> Dynamic {
+    let mut map = Map::new();
+
+    if let Ok((transform, motion)) = things.get(entity) {
+        map.insert("x".into(), Dynamic::from_float(transform.translation.x as FLOAT));
+        map.insert("y".into(), Dynamic::from_float(transform.translation.y as FLOAT));
+        map.insert("rotation".into(), Dynamic::from_float(transform.rotation.to_euler(EulerRot::XYZ).2 as FLOAT));
+        map.insert("vx".into(), Dynamic::from_float(motion.velocity.x as FLOAT));
+        map.insert("vy".into(), Dynamic::from_float(motion.velocity.y as FLOAT));
+    }
+
+    Dynamic::from_map(map)
+}
+
+fn write_back(entity: Entity, view: &Dynamic, things: &mut Query<(&mut Transform, &mut ScriptMotion)>) {
+    let (Some(map), Ok((mut transform, mut motion))) = (view.read_lock::<Map>(), things.get_mut(entity)) else {
+        return;
+    };
+
+    let read = |key: &str| map.get(key).and_then(|value| value.as_float().ok()).map(|value| value as f32);
+
+    if let (Some(x), Some(y)) = (read("x"), read("y")) {
+        transform.translation.x = x;
+        transform.translation.y = y;
+    }
+
+    if let Some(rotation) = read("rotation") {
+        transform.rotation = Quat::from_rotation_z(rotation);
+    }
+
+    if let (Some(vx), Some(vy)) = (read("vx"), read("vy")) {
+        motion.velocity = Vec2::new(vx, vy);
+    }
+}
+
+fn call_hook(engine: &Engine, ast: &AST, this: &mut Dynamic, hook: &str, args: impl rhai::FuncArgs) -> Result<(), String> {
+    if !ast.iter_functions().any(|function| function.name == hook) {
+        return Ok(());
+    }
+
+    let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(this);
+
+    engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, hook, args)
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+// every hook the situation has handled this frame, then the view is copied back and spawns are made
+fn run_hooks(
+    commands: &mut Commands,
+    scripted: &mut ScriptedSituation,
+    things: &mut Query<(&mut Transform, &mut ScriptMotion)>,
+    scripting: &Scripting,
+    script: &Script,
+    asset_server: &Res<AssetServer>,
+    hooks: impl FnOnce(&Engine, &AST, &mut Dynamic) -> Result<(), String>,
+) {
+    let entities: Vec<Entity> = [scripted.good, scripted.bad].into_iter().chain(scripted.spawned.iter().copied()).collect();
+
+    let mut this = Map::new();
+    this.insert("good".into(), view(scripted.good, things));
+    this.insert("bad".into(), view(scripted.bad, things));
+    this.insert("spawned".into(), Dynamic::from_array(scripted.spawned.iter().map(|entity| view(*entity, things)).collect()));
+    this.insert("state".into(), scripted.state.clone());
+    let mut this = Dynamic::from_map(this);
+
+    if let Err(error) = hooks(&scripting.engine, &script.ast, &mut this) {
+        warn!("{}: script stopped until it is edited: {}", scripted.folder, error);
+        scripted.broken = true;
+    }
+
+    // copy the view back onto the real entities
+    if let Some(this) = this.read_lock::<Map>() {
+        let mut views: Vec<Dynamic> = Vec::new();
+
+        for key in ["good", "bad"] {
+            views.push(this.get(key).cloned().unwrap_or_default());
+        }
+
+        if let Some(spawned) = this.get("spawned").and_then(|spawned| spawned.read_lock::<Array>()) {
+            views.extend(spawned.iter().cloned());
+        }
+
+        for (entity, view) in entities.iter().zip(views.iter()) {
+            write_back(*entity, view, things);
+        }
+
+        scripted.state = this.get("state").cloned().unwrap_or_else(|| Dynamic::from_map(Map::new()));
+    }
+
+    let mut despawns: Vec<usize> = scripting.despawns.lock().unwrap().drain(..)
+        .filter_map(|index| usize::try_from(index).ok())
+        .filter(|index| *index < scripted.spawned.len())
+        .collect();
+
+    // highest first, so removing one doesn't shift the rest
+    despawns.sort_unstable_by(|a, b| b.cmp(a));
+    despawns.dedup();
+
+    for index in despawns {
+        commands.entity(scripted.spawned.remove(index)).despawn_recursive();
+    }
+
+    let spawns: Vec<ScriptSpawn> = scripting.spawns.lock().unwrap().drain(..).collect();
+
+    for spawn in spawns {
+        if scripted.spawned.len() >= MAX_SPAWNED {
+            if !scripted.full {
+                warn!("{}: script has {} things spawned, despawn some before spawning more", scripted.folder, MAX_SPAWNED);
+                scripted.full = true;
+            }
+
+            continue;
+        }
+
+        scripted.full = false;
+
+        // scripts can only reach files inside their own pack
+        if spawn.sprite.contains("..") {
+            warn!("{}: script tried to spawn {}", scripted.folder, spawn.sprite);
+            continue;
+        }
+
+        let sprite = format!("{}://{}/{}", MODS_SOURCE, scripted.folder, spawn.sprite);
+
+        let mut entity = match spawn.radius {
+            Some(radius) => {
+                let mut entity = commands.spawn(util::image(spawn.position, sprite, asset_server));
+                entity.insert((BadThing, CircleCollider { radius }));
+                entity
+            },
+            None => commands.spawn(util::image_low(spawn.position, sprite, asset_server)),
+        };
+
+        entity.insert((ScriptMotion::default(), GameEntity, Modded));
+        scripted.spawned.push(entity.id());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_scripts(
+    mut commands: Commands,
+    mut scripted: Query<&mut ScriptedSituation>,
+    mut things: Query<(&mut Transform, &mut ScriptMotion)>,
+    mut rescues: EventReader<Rescue>,
+    scripting: Res<Scripting>,
+    scripts: Res<Assets<Script>>,
+    asset_server: Res<AssetServer>,
+    difficulty: Res<Difficulty>,
+    time: Res<Time>,
+) {
+    let rescued: Vec<Situation> = rescues.read().map(|rescue| rescue.situation).collect();
+
+    for mut scripted in &mut scripted {
+        if scripted.broken {
+            continue;
+        }
+
+        let Some(script) = scripts.get(&scripted.script) else {
+            continue;
+        };
+
+        let situation = scripted.situation;
+        let started = scripted.started;
+        scripted.started = true;
+
+        let dt = time.delta_seconds() as FLOAT;
+        let level = difficulty.of(situation) as FLOAT;
+        let rescues = rescued.iter().filter(|rescued| **rescued == situation).count();
+
+        run_hooks(&mut commands, &mut scripted, &mut things, &scripting, script, &asset_server, |engine, ast, this| {
+            if !started {
+                call_hook(engine, ast, this, "on_spawn", ())?;
+            }
+
+            call_hook(engine, ast, this, "on_tick", (dt, level))?;
+
+            for _ in 0..rescues {
+                call_hook(engine, ast, this, "on_rescue", ())?;
+            }
+
+            Ok(())
+        });
+    }
+}
+
+// collisions are only known in PostUpdate, and by the next frame the run is already over
+fn run_collision_scripts(
+    mut commands: Commands,
+    mut scripted: Query<&mut ScriptedSituation>,
+    mut things: Query<(&mut Transform, &mut ScriptMotion)>,
+    mut collisions: EventReader<Collision>,
+    scripting: Res<Scripting>,
+    scripts: Res<Assets<Script>>,
+    asset_server: Res<AssetServer>,
+) {
+    let collided: Vec<Situation> = collisions.read().map(|collision| collision.situation).collect();
+
+    if collided.is_empty() {
+        return;
+    }
+
+    for mut scripted in &mut scripted {
+        if scripted.broken || !collided.contains(&scripted.situation) {
+            continue;
+        }
+
+        let Some(script) = scripts.get(&scripted.script) else {
+            continue;
+        };
+
+        run_hooks(&mut commands, &mut scripted, &mut things, &scripting, script, &asset_server, |engine, ast, this| {
+            call_hook(engine, ast, this, "on_collision", ())
+        });
+    }
+}
+
+fn move_scripted(mut things: Query<(&mut Transform, &ScriptMotion)>, time: Res<Time>) {
+    for (mut transform, motion) in &mut things {
+        transform.translation += (motion.velocity * time.delta_seconds()).extend(0.);
+    }
+}
+
+fn reload_scripts(mut events: EventReader<AssetEvent<Script>>, mut scripted: Query<&mut ScriptedSituation>) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+
+        for mut scripted in &mut scripted {
+            if scripted.script.id() == *id {
+                info!("{}: script reloaded", scripted.folder);
+                scripted.broken = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_cant_eval() {
+        assert!(sandboxed_engine().compile("fn on_tick(dt, level) { eval(\"this.good.x = 0.0\") }").is_err());
+        assert!(sandboxed_engine().compile("fn on_tick(dt, level) { this.good.x += dt; }").is_ok());
+    }
+
+    #[test]
+    fn scripts_cant_import() {
+        let engine = sandboxed_engine();
+        let ast = engine.compile("import \"other\" as other;").unwrap();
+        assert!(engine.run_ast(&ast).is_err());
+    }
+}
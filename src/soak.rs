@@ -0,0 +1,156 @@
+use bevy::{app::AppExit, prelude::*};
+use crate::ai::AiController;
+use crate::app_state::AppState;
+use crate::game::{DifficultyProfile, EndCause, QueuedSeed, RunEnded, Score};
+use crate::launch::LaunchConfig;
+
+pub struct SoakPlugin {
+    pub runs: usize,
+    pub reaction_time: f32,
+    pub error_rate: f64,
+}
+
+impl SoakPlugin {
+    // do_something soak [runs] [reaction time] [error rate]
+    pub fn from_args(args: &[String]) -> Self {
+        SoakPlugin {
+            runs: args.first().and_then(|arg| arg.parse().ok()).unwrap_or(20),
+            reaction_time: args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(0.25),
+            error_rate: args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(0.05),
+        }
+    }
+}
+
+impl Plugin for SoakPlugin {
+    fn build(&self, app: &mut App) {
+        let launch = app.world.get_resource_or_insert_with(LaunchConfig::default).clone();
+
+        // every preset unless one was asked for
+        let profiles = match launch.difficulty_preset {
+            Some(preset) => vec![preset],
+            None => DifficultyProfile::presets(),
+        };
+
+        app
+        .insert_resource(AiController::new(self.reaction_time, self.error_rate))
+        .insert_resource(Soak {
+            survival: vec![Vec::new(); profiles.len()],
+            profiles,
+            runs_per_profile: self.runs.max(1),
+            max_seconds: 600.,
+            current: 0,
+            started: 0.,
+            seed: launch.seed,
+            runs: 0,
+        })
+        .add_systems(OnEnter(AppState::MainMenu), begin_run)
+        .add_systems(OnEnter(AppState::Game), start_clock)
+        .add_systems(Update, time_limit.run_if(in_state(AppState::Game)))
+        .add_systems(OnEnter(AppState::GameOver), record_run);
+    }
+}
+
+#[derive(Resource)]
+struct Soak {
+    profiles: Vec<DifficultyProfile>,
+    runs_per_profile: usize,
+    max_seconds: f32,
+    current: usize,
+    started: f32,
+    survival: Vec<Vec<f32>>,
+    // with a seed, each run gets the next one along, so the whole soak comes out the same every time
+    seed: Option<u64>,
+    runs: u64,
+}
+
+impl Soak {
+    fn next_run(&mut self, profile: &mut DifficultyProfile, queued: &mut QueuedSeed, next_state: &mut NextState<AppState>) {
+        *profile = self.profiles[self.current].clone();
+        queued.0 = self.seed.map(|seed| seed.wrapping_add(self.runs));
+        self.runs += 1;
+        next_state.set(AppState::Game);
+    }
+}
+
+fn begin_run(mut soak: ResMut<Soak>, mut profile: ResMut<DifficultyProfile>, mut queued: ResMut<QueuedSeed>, mut next_state: ResMut<NextState<AppState>>) {
+    soak.next_run(&mut profile, &mut queued, &mut next_state);
+}
+
+fn start_clock(mut soak: ResMut<Soak>, time: Res<Time>) {
+    soak.started = time.elapsed_seconds();
+}
+
+fn time_limit(
+    soak: Res<Soak>,
+    time: Res<Time>,
+    mut score: ResMut<Score>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut run_ended: EventWriter<RunEnded>,
+) {
+    if time.elapsed_seconds() - soak.started >= soak.max_seconds {
+        score.1 = "outlasted the soak limit".into();
+        next_state.set(AppState::GameOver);
+        run_ended.send(RunEnded { situation: None, cause: EndCause::TimeLimit });
+    }
+}
+
+fn record_run(
+    mut soak: ResMut<Soak>,
+    time: Res<Time>,
+    score: Res<Score>,
+    mut profile: ResMut<DifficultyProfile>,
+    mut queued: ResMut<QueuedSeed>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let survived = time.elapsed_seconds() - soak.started;
+    let current = soak.current;
+    soak.survival[current].push(survived);
+
+    info!("{} run {}: {:.1}s, score {} - {}", profile.name, soak.survival[current].len(), survived, score.0, score.1);
+
+    if soak.survival[current].len() >= soak.runs_per_profile {
+        soak.current += 1;
+    }
+
+    if soak.current >= soak.profiles.len() {
+        report(&soak);
+        exit.send(AppExit);
+        return;
+    }
+
+    soak.next_run(&mut profile, &mut queued, &mut next_state);
+}
+
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[index]
+}
+
+fn report(soak: &Soak) {
+    const BUCKET: f32 = 30.;
+
+    println!("survival times over {} runs per profile", soak.runs_per_profile);
+
+    for (profile, times) in soak.profiles.iter().zip(&soak.survival) {
+        let mut sorted = times.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let mean = sorted.iter().sum::<f32>() / sorted.len() as f32;
+
+        println!();
+        println!(
+            "{} (baby {}, tick {}s): min {:.1}  p25 {:.1}  median {:.1}  p75 {:.1}  max {:.1}  mean {:.1}",
+            profile.name, profile.baby, profile.tick_seconds,
+            sorted[0], percentile(&sorted, 0.25), percentile(&sorted, 0.5), percentile(&sorted, 0.75), sorted[sorted.len() - 1], mean
+        );
+
+        let buckets = (sorted[sorted.len() - 1] / BUCKET) as usize + 1;
+        for bucket in 0..buckets {
+            let low = bucket as f32 * BUCKET;
+            let count = sorted.iter().filter(|time| **time >= low && **time < low + BUCKET).count();
+
+            println!("  {:>4}-{:<4}s | {}", low, low + BUCKET, "#".repeat(count));
+        }
+    }
+}
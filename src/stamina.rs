@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use crate::app_state::AppState;
+use crate::game::{Difficulty, GameEntity, GameSetup, Retired, Situation};
+use crate::generic_ui::{self, Meter};
+use crate::pause;
+use crate::theme::UiTheme;
+
+const MAX_STAMINA: f32 = 100.;
+const REGEN_PER_SECOND: f32 = 12.;
+// every point of difficulty across all situations slows regeneration by this fraction
+const REGEN_SLOWDOWN: f32 = 0.1;
+// given back for ending a situation, on top of it no longer slowing regeneration
+const RETIRE_REFUND: f32 = 50.;
+
+pub fn cost(situation: Situation) -> f32 {
+    match situation {
+        Situation::Baby => 20.,
+        Situation::Sloth => 15.,
+        Situation::Kitten => 35.,
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Stamina { current: MAX_STAMINA, max: MAX_STAMINA }
+    }
+}
+
+impl Stamina {
+    pub fn can_afford(&self, situation: Situation) -> bool {
+        self.current >= cost(situation)
+    }
+
+    pub fn spend(&mut self, situation: Situation) -> bool {
+        if !self.can_afford(situation) {
+            return false;
+        }
+
+        self.current -= cost(situation);
+        true
+    }
+
+    pub fn refund(&mut self) {
+        self.current = (self.current + RETIRE_REFUND).min(self.max);
+    }
+}
+
+#[derive(Component)]
+struct StaminaBar;
+
+pub struct StaminaPlugin;
+
+impl Plugin for StaminaPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Stamina>()
+        .add_systems(OnEnter(AppState::Game), (reset_stamina, spawn_stamina_bar).before(GameSetup))
+        .add_systems(Update, regenerate.run_if(in_state(AppState::Game)).run_if(pause::running))
+        .add_systems(Update, show_stamina.run_if(in_state(AppState::Game)));
+    }
+}
+
+fn reset_stamina(mut stamina: ResMut<Stamina>) {
+    *stamina = Stamina::default();
+}
+
+fn spawn_stamina_bar(mut commands: Commands, theme: Res<UiTheme>) {
+    commands
+        .spawn((generic_ui::anchored(FlexDirection::Column, AlignItems::Start, JustifyContent::Start), GameEntity))
+        .with_children(|parent| {
+            generic_ui::label(parent, &theme, "stamina");
+            generic_ui::meter(parent, &theme, 1., theme.accent).insert(StaminaBar);
+        });
+}
+
+fn regenerate(mut stamina: ResMut<Stamina>, difficulty: Res<Difficulty>, retired: Res<Retired>, time: Res<Time>) {
+    let total: f32 = [Situation::Baby, Situation::Sloth, Situation::Kitten].into_iter()
+        .filter(|situation| !retired.0.contains(situation))
+        .map(|situation| difficulty.of(situation))
+        .sum();
+    let regen = REGEN_PER_SECOND / (1. + total * REGEN_SLOWDOWN);
+
+    if stamina.current < stamina.max {
+        stamina.current = (stamina.current + regen * time.delta_seconds()).min(stamina.max);
+    }
+}
+
+fn show_stamina(stamina: Res<Stamina>, mut bars: Query<&mut Meter, With<StaminaBar>>) {
+    if !stamina.is_changed() {
+        return;
+    }
+
+    for mut bar in &mut bars {
+        bar.value = stamina.current / stamina.max;
+    }
+}
@@ -1,11 +1,33 @@
-use bevy::{core::FrameCount, prelude::*, window::{PresentMode, WindowTheme}};
+use std::time::Duration;
+
+use bevy::{app::{AppExit, PluginGroupBuilder, ScheduleRunnerPlugin}, audio::AudioPlugin, core::FrameCount, log::LogPlugin, prelude::*, render::{settings::WgpuSettings, RenderPlugin}, time::TimeUpdateStrategy, window::{ExitCondition, WindowMode, WindowTheme}, winit::WinitPlugin};
+use crate::camera::CameraRig;
+use crate::launch::{self, LaunchConfig, WindowChoice};
 
 fn startup(mut commands: Commands){
 
-    commands.spawn(Camera2dBundle{
-        ..default()
-    });
+    commands.spawn((
+        Camera2dBundle{
+            ..default()
+        },
+        CameraRig::default(),
+    ));
+
+}
 
+// --ticks quits after that many frames, so test sessions end on their own
+fn exit_after_ticks(frames: Res<FrameCount>, launch: Res<LaunchConfig>, mut exit: EventWriter<AppExit>) {
+    if launch.ticks.is_some_and(|ticks| frames.0 >= ticks) {
+        exit.send(AppExit);
+    }
+}
+
+// --log swaps bevy's logger for one that also writes to the file
+fn with_logging(plugins: PluginGroupBuilder, launch: &LaunchConfig) -> PluginGroupBuilder {
+    match launch.log.as_deref().is_some_and(launch::log_to_file) {
+        true => plugins.disable::<LogPlugin>(),
+        false => plugins,
+    }
 }
 
 fn make_visible(mut window: Query<&mut Window>, frames: Res<FrameCount>) {
@@ -16,19 +38,33 @@ fn make_visible(mut window: Query<&mut Window>, frames: Res<FrameCount>) {
 
 pub struct StartupPlugin;
 
-pub const CLEAR_COLOUR: Color = Color::rgb(154./255., 110./255., 61./255.);
+// defaults for the config file
+pub const TITLE: &str = "do something";
+
+pub const RESOLUTION: (f32, f32)  = (1024., 720.);
 
-const RESOLUTION: (f32, f32)  = (1024., 720.);
+pub const MUSIC: &str = "music.mp3";
 
 impl Plugin for StartupPlugin {
     fn build(&self, app: &mut App){
+        let launch = app.world.get_resource_or_insert_with(LaunchConfig::default).clone();
+        let mode = match launch.window {
+            WindowChoice::Windowed => WindowMode::Windowed,
+            WindowChoice::Fullscreen => WindowMode::BorderlessFullscreen,
+        };
 
-        app.add_plugins(
-            DefaultPlugins.set(WindowPlugin {
+        let volume = match launch.mute {
+            true => 0.,
+            false => launch.volume,
+        };
+
+        let plugins = DefaultPlugins
+            .set(WindowPlugin {
                 primary_window: Some(Window {
-                    title: "do something".into(),
-                    resolution: RESOLUTION.into(),
-                    present_mode: PresentMode::AutoVsync,
+                    title: launch.title.clone(),
+                    resolution: launch.size.into(),
+                    mode,
+                    present_mode: launch.present_mode,
                     prevent_default_event_handling: false,
                     window_theme: Some(WindowTheme::Dark),
                     enabled_buttons: bevy::window::EnabledButtons {
@@ -40,25 +76,70 @@ impl Plugin for StartupPlugin {
                     ..default()
                 }),
                 ..default()
-            }),
-        );
+            })
+            .set(AudioPlugin {
+                global_volume: GlobalVolume::new(volume),
+                ..default()
+            });
 
-        app.add_systems(Startup, play_music);
+        app.add_plugins(with_logging(plugins, &launch));
 
-        app.insert_resource(ClearColor(CLEAR_COLOUR));
+        if !launch.mute && launch.music.is_some() {
+            app.add_systems(Startup, play_music);
+        }
+
+        app.insert_resource(ClearColor(launch.clear_colour));
 
         app.add_systems(Startup, startup);
 
-        app.add_systems(Update, make_visible);
+        app.add_systems(Update, (make_visible, exit_after_ticks));
     }
 }
 
-fn play_music (mut commands: Commands, asset_server: Res<AssetServer>){
+fn play_music (mut commands: Commands, asset_server: Res<AssetServer>, launch: Res<LaunchConfig>){
+    let Some(music) = launch.music.clone() else {
+        return;
+    };
+
     commands.spawn(AudioBundle {
-        source: asset_server.load("music.mp3"),
+        source: asset_server.load(music),
         settings: PlaybackSettings {
             mode: bevy::audio::PlaybackMode::Loop,
             ..default()
         }
     });
-}
\ No newline at end of file
+}
+
+pub struct HeadlessPlugin;
+
+pub const HEADLESS_TICK: f32 = 1. / 60.;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App){
+        let launch = app.world.get_resource_or_insert_with(LaunchConfig::default).clone();
+
+        let plugins = DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..default()
+                }.into()
+            })
+            .disable::<WinitPlugin>();
+
+        app.add_plugins(with_logging(plugins, &launch));
+
+        app.add_systems(Update, exit_after_ticks);
+
+        app.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO));
+
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(HEADLESS_TICK)));
+
+        app.insert_resource(ClearColor(launch.clear_colour));
+    }
+}
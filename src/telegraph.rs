@@ -0,0 +1,270 @@
+use bevy::{prelude::*, transform::TransformSystem, utils::HashSet};
+use crate::app_state::AppState;
+use crate::broadphase::SpatialGrid;
+use crate::game::{BadThing, CircleCollider, Difficulty, GameEntity, GoodThing, RescueCooldown, RunClock, Situation};
+use crate::generic_ui::Focus;
+use crate::hazards::{Dormant, PeriodicActiveWindow, Rotate};
+use crate::pause;
+use crate::theme::UiTheme;
+use crate::tween::lerp_colour;
+
+// how far ahead contacts are searched for, and how finely
+const STEP: f32 = 1. / 30.;
+// anything moving further than this in one frame was teleported, not moving
+const MAX_STEP_DISTANCE: f32 = 100.;
+// swept hazards cover a lot more ground than still ones, so they get bigger cells
+const SWEEP_CELL_SIZE: f32 = 256.;
+
+#[derive(Resource, Debug, Clone)]
+pub struct TelegraphSettings {
+    pub threshold: f32,
+    pub horizon: f32,
+}
+
+impl Default for TelegraphSettings {
+    fn default() -> Self {
+        TelegraphSettings {
+            threshold: 1.,
+            horizon: 2.,
+        }
+    }
+}
+
+// world space velocity measured from frame to frame
+#[derive(Component, Default)]
+pub struct Tracked {
+    previous: Option<Vec2>,
+    pub velocity: Vec2,
+}
+
+// on a good thing that is predicted to be hit within the threshold
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Threat {
+    pub time_to_contact: f32,
+}
+
+pub struct TelegraphPlugin;
+
+impl Plugin for TelegraphPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<TelegraphSettings>()
+        .add_systems(PostUpdate, (track_velocity, predict_contacts)
+            .chain()
+            .after(TransformSystem::TransformPropagate)
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+        )
+        .add_systems(Update, (warning_cue, flash_threatened, pulse_rescue_buttons).run_if(in_state(AppState::Game)));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn track_velocity(
+    mut commands: Commands,
+    untracked: Query<Entity, (Without<Tracked>, Or<(With<GoodThing>, With<BadThing>)>)>,
+    mut tracked: Query<(&mut Tracked, &GlobalTransform)>,
+    time: Res<Time>,
+) {
+    for entity in &untracked {
+        commands.entity(entity).insert(Tracked::default());
+    }
+
+    let delta = time.delta_seconds();
+
+    if delta <= 0. {
+        return;
+    }
+
+    for (mut tracked, transform) in &mut tracked {
+        let position = transform.translation().xy();
+
+        tracked.velocity = match tracked.previous {
+            Some(previous) if previous.distance(position) < MAX_STEP_DISTANCE => (position - previous) / delta,
+            _ => Vec2::ZERO,
+        };
+        tracked.previous = Some(position);
+    }
+}
+
+struct Mover {
+    position: Vec2,
+    velocity: Vec2,
+    // centre and angular velocity of a rotating parent, which wins over velocity
+    orbit: Option<(Vec2, f32)>,
+    reach: f32,
+}
+
+impl Mover {
+    fn at(&self, time: f32) -> Vec2 {
+        match self.orbit {
+            Some((centre, angular_velocity)) => centre + Vec2::from_angle(angular_velocity * time).rotate(self.position - centre),
+            None => self.position + self.velocity * time,
+        }
+    }
+
+    // furthest it can get from where it is now within the horizon
+    fn sweep(&self, horizon: f32) -> f32 {
+        match self.orbit {
+            Some((centre, angular_velocity)) => {
+                let radius = self.position.distance(centre);
+                (angular_velocity.abs() * radius * horizon).min(radius * 2.)
+            },
+            None => self.velocity.length() * horizon,
+        }
+    }
+}
+
+fn time_to_contact(good: &Mover, bad: &Mover, window: Option<&PeriodicActiveWindow>, now: f32, horizon: f32) -> Option<f32> {
+    let mut time = 0.;
+
+    while time <= horizon {
+        let touching = good.at(time).distance(bad.at(time)) <= good.reach + bad.reach;
+        let active = window.is_none_or(|window| window.is_active(now + time));
+
+        if touching && active {
+            return Some(time);
+        }
+
+        time += STEP;
+    }
+
+    None
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn predict_contacts(
+    mut commands: Commands,
+    settings: Res<TelegraphSettings>,
+    clock: Res<RunClock>,
+    difficulty: Res<Difficulty>,
+    good_things: Query<(Entity, &CircleCollider, &GlobalTransform, &Tracked, Option<&Threat>), With<GoodThing>>,
+    bad_things: Query<(&CircleCollider, &GlobalTransform, &Tracked, Option<&Parent>, Option<&PeriodicActiveWindow>, Has<Dormant>), With<BadThing>>,
+    rotating: Query<(&GlobalTransform, &Rotate)>,
+) {
+    let now = clock.0.elapsed_secs();
+
+    let bad_movers: Vec<(Mover, Option<&PeriodicActiveWindow>)> = bad_things.iter()
+        // dormant hazards only count if they wake up on a timer
+        .filter(|(_, _, _, _, window, dormant)| !dormant || window.is_some())
+        .map(|(circle, transform, tracked, parent, window, _)| {
+            let orbit = parent
+                .and_then(|parent| rotating.get(parent.get()).ok())
+                .map(|(centre, rotate)| (centre.translation().xy(), rotate.angular_velocity(&difficulty)));
+
+            let mover = Mover {
+                position: transform.translation().xy(),
+                velocity: tracked.velocity,
+                orbit,
+                reach: circle.radius * transform.compute_transform().scale.x,
+            };
+
+            (mover, window)
+        })
+        .collect();
+
+    // only pairs that could close the distance before the horizon are stepped through
+    let mut reachable = SpatialGrid::new(SWEEP_CELL_SIZE);
+
+    for (index, (bad, _)) in bad_movers.iter().enumerate() {
+        reachable.insert(index, bad.position, bad.reach + bad.sweep(settings.horizon));
+    }
+
+    for (entity, circle, transform, tracked, threat) in &good_things {
+        let good = Mover {
+            position: transform.translation().xy(),
+            velocity: tracked.velocity,
+            orbit: None,
+            reach: circle.radius * transform.compute_transform().scale.x,
+        };
+
+        let soonest = reachable.overlapping(good.position, good.reach + good.sweep(settings.horizon))
+            .map(|(index, _, _)| &bad_movers[index])
+            .filter_map(|(bad, window)| time_to_contact(&good, bad, *window, now, settings.horizon))
+            .reduce(f32::min);
+
+        match (soonest.filter(|time| *time <= settings.threshold), threat) {
+            (Some(time_to_contact), _) => {
+                commands.entity(entity).insert(Threat { time_to_contact });
+            },
+            (None, Some(_)) => {
+                commands.entity(entity).remove::<Threat>();
+            },
+            (None, None) => {},
+        }
+    }
+}
+
+fn warning_cue(mut commands: Commands, asset_server: Res<AssetServer>, threatened: Query<(), Added<Threat>>) {
+    if threatened.is_empty() {
+        return;
+    }
+
+    commands.spawn((
+        AudioBundle {
+            source: asset_server.load("warning.wav"),
+            settings: PlaybackSettings::DESPAWN,
+        },
+        GameEntity,
+    ));
+}
+
+// 0 to 1 and back, quicker the closer the contact
+fn pulse(time: f32, time_to_contact: f32) -> f32 {
+    let speed = 6. + 12. * (1. - time_to_contact).max(0.);
+    0.5 + 0.5 * (time * speed).sin()
+}
+
+fn flash_threatened(
+    mut sprites: Query<(&mut Sprite, Option<&Threat>), With<GoodThing>>,
+    mut cleared: RemovedComponents<Threat>,
+    theme: Res<UiTheme>,
+    time: Res<Time<Real>>,
+) {
+    let cleared: HashSet<Entity> = cleared.read().collect();
+
+    for (mut sprite, threat) in &mut sprites {
+        if let Some(threat) = threat {
+            let amount = pulse(time.elapsed_seconds(), threat.time_to_contact);
+            sprite.color = lerp_colour(Color::WHITE, theme.danger, amount);
+        }
+    }
+
+    for entity in cleared {
+        if let Ok((mut sprite, _)) = sprites.get_mut(entity) {
+            sprite.color = Color::WHITE;
+        }
+    }
+}
+
+fn pulse_rescue_buttons(
+    mut buttons: Query<(Entity, &RescueCooldown, &mut BorderColor)>,
+    threats: Query<(&GoodThing, &Threat)>,
+    focus: Res<Focus>,
+    theme: Res<UiTheme>,
+    time: Res<Time<Real>>,
+) {
+    let soonest = |situation: Situation| threats.iter()
+        .filter(|(good_thing, _)| good_thing.situation == situation)
+        .map(|(_, threat)| threat.time_to_contact)
+        .reduce(f32::min);
+
+    for (entity, rescue, mut border) in &mut buttons {
+        let resting = match focus.0 == Some(entity) {
+            true => theme.focus,
+            false => theme.border,
+        };
+
+        let colour = match soonest(rescue.situation) {
+            Some(time_to_contact) => {
+                let amount = pulse(time.elapsed_seconds(), time_to_contact);
+                lerp_colour(resting, theme.danger, amount)
+            },
+            None => resting,
+        };
+
+        if border.0 != colour {
+            border.0 = colour;
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use crate::app_state::AppState;
+use crate::combo::{NearMiss, ScoreBreakdown};
+use crate::game::{BadThing, Collision, CollisionSet, Difficulty, DifficultyProfile, EndCause, GoodThing, Rescue, RunEnded, RunSeed, Score, Situation, SituationRetired, SituationUnlocked};
+use crate::milestones::{MilestoneReached, MilestoneSet};
+use crate::pause;
+use crate::players::GameMode;
+
+// how many frame deltas go on each frames line
+const FRAMES_PER_RECORD: usize = 60;
+
+#[derive(Resource)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    pub directory: PathBuf,
+}
+
+impl Default for TelemetrySettings {
+    fn default() -> Self {
+        TelemetrySettings {
+            enabled: true,
+            directory: PathBuf::from("logs"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Placed {
+    situation: Situation,
+    x: f32,
+    y: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Record {
+    RunStart {
+        mode: String,
+        profile: String,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    State { state: String },
+    Difficulty { baby: f32, sloth: f32, kitten: f32 },
+    Unlock { situation: Situation },
+    Milestone { name: String },
+    Rescue { situation: Situation, good: Vec<Placed>, bad: Vec<[f32; 2]> },
+    Retire { situation: Situation },
+    Collision { situation: Situation, good: [f32; 2], bad: [f32; 2] },
+    NearMiss { situation: Situation, points: i32, multiplier: f32 },
+    // how long each running frame was, batched so a replay can step through the run exactly
+    Frames { deltas: Vec<f32> },
+    End {
+        cause: EndCause,
+        situation: Option<Situation>,
+        message: String,
+        score: i32,
+        survived: f32,
+        #[serde(default)]
+        bonus: i32,
+        #[serde(default)]
+        near_misses: u32,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    t: f32,
+    // running frames since the run started, logs from before this was added have none
+    #[serde(default)]
+    frame: u32,
+    #[serde(flatten)]
+    record: Record,
+}
+
+#[derive(Resource, Default)]
+struct RunLog {
+    writer: Option<BufWriter<File>>,
+    started: f32,
+    ended: Option<(Option<Situation>, EndCause)>,
+    frame: u32,
+    deltas: Vec<f32>,
+}
+
+impl RunLog {
+    fn write(&mut self, now: f32, record: Record) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+
+        let line = Line { t: now - self.started, frame: self.frame, record };
+
+        let written = serde_json::to_writer(&mut *writer, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+
+        if let Err(error) = written {
+            warn!("telemetry disabled for this run: {}", error);
+            self.writer = None;
+        }
+    }
+
+    fn flush_frames(&mut self, now: f32) {
+        if !self.deltas.is_empty() {
+            let deltas = std::mem::take(&mut self.deltas);
+            self.write(now, Record::Frames { deltas });
+        }
+    }
+}
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<TelemetrySettings>()
+        .init_resource::<RunLog>()
+        // a resumed run has put its seed back by the time milestones are checked
+        .add_systems(OnEnter(AppState::Game), open_log.after(MilestoneSet))
+        // on leaving the run however it ends, save & quit included, so every log gets its end record
+        .add_systems(OnExit(AppState::Game), close_log)
+        .add_systems(Update, count_frame
+            .run_if(in_state(AppState::Game))
+            .run_if(pause::running)
+            .run_if(|settings: Res<TelemetrySettings>| settings.enabled)
+        )
+        .add_systems(PostUpdate, (
+            log_state,
+            log_difficulty,
+            log_events,
+        )
+            .chain()
+            .after(CollisionSet)
+            .run_if(|settings: Res<TelemetrySettings>| settings.enabled)
+        );
+    }
+}
+
+fn open_log(
+    mut log: ResMut<RunLog>,
+    settings: Res<TelemetrySettings>,
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    profile: Res<DifficultyProfile>,
+    seed: Res<RunSeed>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_millis()).unwrap_or_default();
+    let path = settings.directory.join(format!("run-{}.jsonl", stamp));
+
+    let file = fs::create_dir_all(&settings.directory).and_then(|_| File::create(&path));
+
+    match file {
+        Ok(file) => {
+            log.writer = Some(BufWriter::new(file));
+            log.started = time.elapsed_seconds();
+            log.ended = None;
+            log.frame = 0;
+            log.deltas.clear();
+            log.write(time.elapsed_seconds(), Record::RunStart { mode: format!("{:?}", *mode), profile: profile.name.clone(), seed: Some(seed.0) });
+        },
+        Err(error) => warn!("could not open telemetry log {}: {}", path.display(), error),
+    }
+}
+
+fn close_log(mut log: ResMut<RunLog>, time: Res<Time>, score: Res<Score>, breakdown: Res<ScoreBreakdown>, state: Res<State<AppState>>) {
+    let (situation, cause) = log.ended.unwrap_or((None, EndCause::Abandoned));
+    let survived = time.elapsed_seconds() - log.started;
+
+    log.flush_frames(time.elapsed_seconds());
+    log.write(time.elapsed_seconds(), Record::State { state: format!("{:?}", state.get()) });
+    log.write(time.elapsed_seconds(), Record::End {
+        cause,
+        situation,
+        message: score.1.clone(),
+        score: score.0,
+        survived,
+        bonus: breakdown.bonus,
+        near_misses: breakdown.near_misses,
+    });
+
+    if let Some(mut writer) = log.writer.take() {
+        if let Err(error) = writer.flush() {
+            warn!("could not flush telemetry log: {}", error);
+        }
+    }
+}
+
+// every event logged in a frame carries its number, and the frame's delta is kept for replays
+fn count_frame(mut log: ResMut<RunLog>, time: Res<Time>) {
+    if log.writer.is_none() {
+        return;
+    }
+
+    log.frame += 1;
+    log.deltas.push(time.delta_seconds());
+
+    if log.deltas.len() >= FRAMES_PER_RECORD {
+        log.flush_frames(time.elapsed_seconds());
+    }
+}
+
+fn log_state(mut log: ResMut<RunLog>, state: Res<State<AppState>>, time: Res<Time>) {
+    if state.is_changed() {
+        log.write(time.elapsed_seconds(), Record::State { state: format!("{:?}", state.get()) });
+    }
+}
+
+fn log_difficulty(mut log: ResMut<RunLog>, difficulty: Option<Res<Difficulty>>, time: Res<Time>) {
+    let Some(difficulty) = difficulty else {
+        return;
+    };
+
+    if difficulty.is_changed() {
+        log.write(time.elapsed_seconds(), Record::Difficulty {
+            baby: difficulty.baby,
+            sloth: difficulty.sloth,
+            kitten: difficulty.kitten,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn log_events(
+    mut log: ResMut<RunLog>,
+    time: Res<Time>,
+    mut unlocks: EventReader<SituationUnlocked>,
+    mut milestones: EventReader<MilestoneReached>,
+    mut rescues: EventReader<Rescue>,
+    mut retirements: EventReader<SituationRetired>,
+    mut collisions: EventReader<Collision>,
+    mut near_misses: EventReader<NearMiss>,
+    mut run_ended: EventReader<RunEnded>,
+    good_things: Query<(&GoodThing, &GlobalTransform)>,
+    bad_things: Query<&GlobalTransform, With<BadThing>>,
+) {
+    let now = time.elapsed_seconds();
+
+    for unlock in unlocks.read() {
+        log.write(now, Record::Unlock { situation: unlock.0 });
+    }
+
+    for milestone in milestones.read() {
+        log.write(now, Record::Milestone { name: milestone.0.clone() });
+    }
+
+    for rescue in rescues.read() {
+        let good = good_things.iter()
+            .map(|(good_thing, transform)| Placed {
+                situation: good_thing.situation,
+                x: transform.translation().x,
+                y: transform.translation().y,
+            })
+            .collect();
+
+        let bad = bad_things.iter()
+            .map(|transform| transform.translation().xy().to_array())
+            .collect();
+
+        log.write(now, Record::Rescue { situation: rescue.situation, good, bad });
+    }
+
+    for retirement in retirements.read() {
+        log.write(now, Record::Retire { situation: retirement.0 });
+    }
+
+    for near_miss in near_misses.read() {
+        log.write(now, Record::NearMiss {
+            situation: near_miss.situation,
+            points: near_miss.points,
+            multiplier: near_miss.multiplier,
+        });
+    }
+
+    for collision in collisions.read() {
+        log.write(now, Record::Collision {
+            situation: collision.situation,
+            good: collision.good.to_array(),
+            bad: collision.bad.to_array(),
+        });
+    }
+
+    for ended in run_ended.read() {
+        log.ended.get_or_insert((ended.situation, ended.cause));
+    }
+}
+
+// what a replay needs from a run's log: how it started, how long each frame was and which frame each rescue and retirement happened on
+pub struct RecordedRun {
+    pub seed: Option<u64>,
+    pub mode: GameMode,
+    pub profile: String,
+    pub deltas: Vec<f32>,
+    pub rescues: Vec<(u32, Situation)>,
+    pub retirements: Vec<(u32, Situation)>,
+}
+
+pub fn read_run(path: &Path) -> Result<RecordedRun, String> {
+    let file = File::open(path).map_err(|error| format!("could not open {}: {}", path.display(), error))?;
+
+    let mut run: Option<RecordedRun> = None;
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+        let line: Line = serde_json::from_str(&line).map_err(|error| format!("{} line {}: {}", path.display(), number + 1, error))?;
+
+        match (line.record, run.as_mut()) {
+            (Record::RunStart { mode, profile, seed }, None) => {
+                run = Some(RecordedRun { seed, mode: parse_mode(&mode), profile, deltas: Vec::new(), rescues: Vec::new(), retirements: Vec::new() });
+            },
+            (Record::Frames { deltas }, Some(run)) => run.deltas.extend(deltas),
+            (Record::Rescue { situation, .. }, Some(run)) => run.rescues.push((line.frame, situation)),
+            (Record::Retire { situation }, Some(run)) => run.retirements.push((line.frame, situation)),
+            _ => {},
+        }
+    }
+
+    let run = run.ok_or_else(|| format!("{} has no run_start record", path.display()))?;
+
+    if run.deltas.is_empty() {
+        return Err(format!("{} was recorded without frame timings and can't be replayed", path.display()));
+    }
+
+    if run.seed.is_none() {
+        warn!("{} was recorded without a seed, the replay will drift", path.display());
+    }
+
+    Ok(run)
+}
+
+// run_start writes the mode with Debug
+fn parse_mode(mode: &str) -> GameMode {
+    match mode {
+        "Coop" => GameMode::Coop,
+        _ => GameMode::Solo,
+    }
+}
+
+// one finished run as the history screen lists it
+#[derive(Debug, Clone)]
+pub struct PastRun {
+    pub path: PathBuf,
+    // milliseconds since the epoch, from the log's file name
+    pub started: u64,
+    pub mode: GameMode,
+    pub profile: String,
+    pub seed: Option<u64>,
+    pub score: i32,
+    pub bonus: i32,
+    pub survived: f32,
+    pub cause: EndCause,
+    pub situation: Option<Situation>,
+    pub message: String,
+    pub near_misses: u32,
+    pub rescues: usize,
+    pub unlocks: Vec<Situation>,
+}
+
+fn read_past_run(path: &Path) -> Option<PastRun> {
+    let started = path.file_stem()
+        .and_then(OsStr::to_str)
+        .and_then(|stem| stem.strip_prefix("run-"))
+        .and_then(|stamp| stamp.parse().ok())?;
+
+    let file = File::open(path).ok()?;
+
+    let mut start = None;
+    let mut rescues = 0;
+    let mut unlocks = Vec::new();
+
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let Ok(line) = serde_json::from_str::<Line>(&line) else {
+            continue;
+        };
+
+        match line.record {
+            Record::RunStart { mode, profile, seed } => start = Some((parse_mode(&mode), profile, seed)),
+            Record::Rescue { .. } => rescues += 1,
+            Record::Unlock { situation } => unlocks.push(situation),
+            Record::End { cause, situation, message, score, survived, bonus, near_misses } => {
+                let (mode, profile, seed) = start?;
+
+                return Some(PastRun {
+                    path: path.to_path_buf(),
+                    started,
+                    mode,
+                    profile,
+                    seed,
+                    score,
+                    bonus,
+                    survived,
+                    cause,
+                    situation,
+                    message,
+                    near_misses,
+                    rescues,
+                    unlocks,
+                });
+            },
+            _ => {},
+        }
+    }
+
+    // the game was closed mid-run
+    None
+}
+
+// every finished run in the directory, unreadable and unfinished logs are left out
+pub fn read_history(directory: &Path) -> Vec<PastRun> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension() == Some(OsStr::new("jsonl")))
+        .filter_map(|path| read_past_run(&path))
+        .collect()
+}
+
+// do_something summarize [directory]
+pub fn summarize(directory: &Path) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            eprintln!("could not read {}: {}", directory.display(), error);
+            return;
+        }
+    };
+
+    let mut survival: Vec<f32> = Vec::new();
+    let mut deaths: HashMap<Option<Situation>, usize> = HashMap::new();
+    let mut unreadable = 0;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.extension() != Some(OsStr::new("jsonl")) {
+            continue;
+        }
+
+        let Ok(file) = File::open(&path) else {
+            unreadable += 1;
+            continue;
+        };
+
+        let end = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<Line>(&line).ok())
+            .find_map(|line| match line.record {
+                Record::End { situation, cause, survived, .. } => Some((situation, cause, survived)),
+                _ => None,
+            });
+
+        match end {
+            // nothing ended these, they only stopped
+            Some((_, EndCause::Abandoned, _)) => {},
+            Some((situation, cause, survived)) => {
+                survival.push(survived);
+
+                if cause != EndCause::TimeLimit {
+                    *deaths.entry(situation).or_default() += 1;
+                }
+            },
+            None => unreadable += 1,
+        }
+    }
+
+    if survival.is_empty() {
+        println!("no finished runs in {}", directory.display());
+        return;
+    }
+
+    survival.sort_by(|a, b| a.total_cmp(b));
+
+    let middle = survival.len() / 2;
+    let median = match survival.len() % 2 {
+        0 => (survival[middle - 1] + survival[middle]) / 2.,
+        _ => survival[middle],
+    };
+    let mean = survival.iter().sum::<f32>() / survival.len() as f32;
+
+    println!("runs: {} ({} unreadable or unfinished)", survival.len(), unreadable);
+    println!("survival: median {:.1}s, mean {:.1}s, longest {:.1}s", median, mean, survival[survival.len() - 1]);
+
+    let mut deaths: Vec<(Option<Situation>, usize)> = deaths.into_iter().collect();
+    deaths.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    if let Some((situation, count)) = deaths.first() {
+        println!("most lethal: {} ({} runs)", situation_name(*situation), count);
+    }
+
+    for (situation, count) in &deaths {
+        println!("  {:<8} {}", situation_name(*situation), count);
+    }
+}
+
+fn situation_name(situation: Option<Situation>) -> String {
+    match situation {
+        Some(situation) => format!("{:?}", situation).to_lowercase(),
+        None => "unknown".into(),
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use crate::players::Player;
+
+// everything the widgets in generic_ui draw with, so a restyle only touches this file
+#[derive(Resource, Clone)]
+pub struct UiTheme {
+    pub font: Handle<Font>,
+    pub text: Color,
+    pub danger: Color,
+    pub button: Color,
+    pub button_hovered: Color,
+    pub button_pressed: Color,
+    pub border: Color,
+    pub focus: Color,
+    pub accent: Color,
+    pub panel: Color,
+    pub overlay: Color,
+    pub game_over_background: Color,
+    // each player's buttons, idle, hovered and pressed
+    pub player_buttons: [[Color; 3]; 2],
+    pub title_size: f32,
+    pub text_size: f32,
+    pub small_text_size: f32,
+    pub button_size: Vec2,
+    pub slider_size: Vec2,
+    pub row_height: f32,
+    pub border_width: f32,
+    pub spacing: f32,
+}
+
+impl FromWorld for UiTheme {
+    fn from_world(world: &mut World) -> Self {
+        UiTheme {
+            font: world.resource::<AssetServer>().load("PoorStory-Regular.ttf"),
+            text: Color::rgb(0.9, 0.9, 0.9),
+            danger: Color::rgb(0.9, 0.3, 0.3),
+            button: Color::rgb(0.15, 0.15, 0.15),
+            button_hovered: Color::rgb(0.25, 0.25, 0.25),
+            button_pressed: Color::rgb(0.35, 0.35, 0.35),
+            border: Color::BLACK,
+            focus: Color::rgb(0.95, 0.85, 0.45),
+            accent: Color::rgb(0.55, 0.45, 0.2),
+            panel: Color::rgb(0.1, 0.1, 0.1),
+            overlay: Color::rgba(0., 0., 0., 0.6),
+            game_over_background: Color::rgb(47./255., 31./255., 13./255.),
+            player_buttons: [
+                [Color::rgb(0.15, 0.2, 0.45), Color::rgb(0.25, 0.3, 0.55), Color::rgb(0.35, 0.4, 0.65)],
+                [Color::rgb(0.45, 0.15, 0.2), Color::rgb(0.55, 0.25, 0.3), Color::rgb(0.65, 0.35, 0.4)],
+            ],
+            title_size: 60.,
+            text_size: 40.,
+            small_text_size: 30.,
+            button_size: Vec2::new(200., 125.),
+            slider_size: Vec2::new(300., 30.),
+            row_height: 50.,
+            border_width: 5.,
+            spacing: 5.,
+        }
+    }
+}
+
+impl UiTheme {
+    // the playfield's background, a constant so the config file and flags can default to it before any theme exists
+    pub const CLEAR_COLOUR: Color = Color::rgb(154./255., 110./255., 61./255.);
+
+    pub fn text_style(&self, size: f32, color: Color) -> TextStyle {
+        TextStyle {
+            font: self.font.clone(),
+            font_size: size,
+            color,
+        }
+    }
+
+    pub fn button_colour(&self, interaction: Interaction) -> Color {
+        match interaction {
+            Interaction::Pressed => self.button_pressed,
+            Interaction::Hovered => self.button_hovered,
+            Interaction::None => self.button,
+        }
+    }
+
+    pub fn player_colour(&self, player: Player, interaction: Interaction) -> Color {
+        let [idle, hovered, pressed] = self.player_buttons[player.index()];
+
+        match interaction {
+            Interaction::Pressed => pressed,
+            Interaction::Hovered => hovered,
+            Interaction::None => idle,
+        }
+    }
+}
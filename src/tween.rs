@@ -0,0 +1,271 @@
+use std::f32::consts::PI;
+
+use bevy::{ecs::system::Command, prelude::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    BackOut,
+}
+
+impl Ease {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1. - (1. - t) * (1. - t),
+            Ease::QuadInOut => match t < 0.5 {
+                true => 2. * t * t,
+                false => 1. - (-2. * t + 2.).powi(2) / 2.,
+            },
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1. - (1. - t).powi(3),
+            Ease::SineIn => 1. - (t * PI / 2.).cos(),
+            Ease::SineOut => (t * PI / 2.).sin(),
+            Ease::SineInOut => -((PI * t).cos() - 1.) / 2.,
+            Ease::BackOut => {
+                const C1: f32 = 1.70158;
+                const C3: f32 = C1 + 1.;
+                1. + C3 * (t - 1.).powi(3) + C1 * (t - 1.).powi(2)
+            },
+        }
+    }
+}
+
+// the *By lenses add their change frame by frame so they compose with other motion,
+// the rest set an absolute value
+#[derive(Debug, Clone, Copy)]
+pub enum Lens {
+    TranslateBy(Vec3),
+    MoveTo { from: Vec3, to: Vec3 },
+    RotateBy(f32),
+    RotateTo { from: f32, to: f32 },
+    ScaleTo { from: Vec3, to: Vec3 },
+    SpriteColour { from: Color, to: Color },
+    UiSize { from: Vec2, to: Vec2 },
+    UiBackground { from: Color, to: Color },
+    TextColour { from: Color, to: Color },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    Once,
+    Forever,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    lens: Lens,
+    seconds: f32,
+    ease: Ease,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tween {
+    steps: Vec<Step>,
+    repeat: Repeat,
+    id: u32,
+    index: usize,
+    elapsed: f32,
+}
+
+impl Tween {
+    pub fn new(lens: Lens, seconds: f32, ease: Ease) -> Self {
+        Tween {
+            steps: Vec::new(),
+            repeat: Repeat::Once,
+            id: 0,
+            index: 0,
+            elapsed: 0.,
+        }
+        .then(lens, seconds, ease)
+    }
+
+    pub fn then(mut self, lens: Lens, seconds: f32, ease: Ease) -> Self {
+        self.steps.push(Step { lens, seconds: seconds.max(0.0001), ease });
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
+    // moves the playhead without applying anything, for lining tweens up with a clock
+    pub fn seek(mut self, seconds: f32) -> Self {
+        let total: f32 = self.steps.iter().map(|step| step.seconds).sum();
+
+        let mut remaining = match self.repeat {
+            Repeat::Forever => seconds % total,
+            Repeat::Once => seconds.min(total),
+        };
+
+        self.index = 0;
+        while self.index < self.steps.len() - 1 && remaining >= self.steps[self.index].seconds {
+            remaining -= self.steps[self.index].seconds;
+            self.index += 1;
+        }
+        self.elapsed = remaining;
+
+        self
+    }
+
+    // returns the (step, from, to) progress spans covered by this tick and whether the tween is done
+    fn advance(&mut self, mut delta: f32) -> (Vec<(usize, f32, f32)>, bool) {
+        let mut spans = Vec::new();
+
+        while delta > 0. {
+            let step = &self.steps[self.index];
+            let from = self.elapsed / step.seconds;
+            let available = step.seconds - self.elapsed;
+
+            if delta < available {
+                self.elapsed += delta;
+                spans.push((self.index, from, self.elapsed / step.seconds));
+                return (spans, false);
+            }
+
+            spans.push((self.index, from, 1.));
+            delta -= available;
+            self.elapsed = 0.;
+            self.index += 1;
+
+            if self.index == self.steps.len() {
+                self.index = 0;
+
+                if self.repeat == Repeat::Once {
+                    return (spans, true);
+                }
+            }
+        }
+
+        (spans, false)
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Tweens(pub Vec<Tween>);
+
+#[derive(Event)]
+pub struct TweenCompleted {
+    pub entity: Entity,
+    pub id: u32,
+}
+
+pub struct AddTween {
+    pub entity: Entity,
+    pub tween: Tween,
+}
+
+impl Command for AddTween {
+    fn apply(self, world: &mut World) {
+        let Some(mut entity) = world.get_entity_mut(self.entity) else {
+            return;
+        };
+
+        match entity.get_mut::<Tweens>() {
+            Some(mut tweens) => tweens.0.push(self.tween),
+            None => {
+                entity.insert(Tweens(vec![self.tween]));
+            },
+        }
+    }
+}
+
+pub fn tween(entity: Entity, tween: Tween) -> AddTween {
+    AddTween { entity, tween }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TweenSet;
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_event::<TweenCompleted>()
+        .add_systems(Update, advance_tweens.in_set(TweenSet));
+    }
+}
+
+pub fn lerp_colour(from: Color, to: Color, t: f32) -> Color {
+    let from = Vec4::from_array(from.as_rgba_f32());
+    let to = Vec4::from_array(to.as_rgba_f32());
+    let mixed = from.lerp(to, t);
+
+    Color::rgba(mixed.x, mixed.y, mixed.z, mixed.w)
+}
+
+type TweenTargets<'a> = (
+    Entity,
+    &'a mut Tweens,
+    Option<&'a mut Transform>,
+    Option<&'a mut Sprite>,
+    Option<&'a mut Style>,
+    Option<&'a mut BackgroundColor>,
+    Option<&'a mut Text>,
+);
+
+fn advance_tweens(
+    mut query: Query<TweenTargets>,
+    time: Res<Time>,
+    mut completed: EventWriter<TweenCompleted>,
+) {
+    for (entity, mut tweens, mut transform, mut sprite, mut style, mut background, mut text) in &mut query {
+        tweens.0.retain_mut(|tween| {
+            let (spans, finished) = tween.advance(time.delta_seconds());
+
+            for (index, from, to) in spans {
+                let step = &tween.steps[index];
+                let eased_from = step.ease.apply(from);
+                let eased = step.ease.apply(to);
+
+                match (step.lens, transform.as_deref_mut(), sprite.as_deref_mut(), style.as_deref_mut(), background.as_deref_mut()) {
+                    (Lens::TranslateBy(by), Some(transform), ..) => transform.translation += by * (eased - eased_from),
+                    (Lens::MoveTo { from, to }, Some(transform), ..) => transform.translation = from.lerp(to, eased),
+                    (Lens::RotateBy(by), Some(transform), ..) => transform.rotate_z(by * (eased - eased_from)),
+                    (Lens::RotateTo { from, to }, Some(transform), ..) => transform.rotation = Quat::from_rotation_z(from + (to - from) * eased),
+                    (Lens::ScaleTo { from, to }, Some(transform), ..) => transform.scale = from.lerp(to, eased),
+                    (Lens::SpriteColour { from, to }, _, Some(sprite), ..) => sprite.color = lerp_colour(from, to, eased),
+                    (Lens::UiSize { from, to }, _, _, Some(style), _) => {
+                        let size = from.lerp(to, eased);
+                        style.width = Val::Px(size.x);
+                        style.height = Val::Px(size.y);
+                    },
+                    (Lens::UiBackground { from, to }, _, _, _, Some(background)) => background.0 = lerp_colour(from, to, eased),
+                    (Lens::TextColour { from, to }, ..) => {
+                        if let Some(text) = text.as_deref_mut() {
+                            for section in &mut text.sections {
+                                section.style.color = lerp_colour(from, to, eased);
+                            }
+                        }
+                    },
+                    _ => {},
+                }
+            }
+
+            if finished {
+                completed.send(TweenCompleted { entity, id: tween.id });
+            }
+
+            !finished
+        });
+    }
+}